@@ -0,0 +1,163 @@
+//! Iterator adapters selecting the variants of a token.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::{
+    EnumVariantDowncast, EnumVariantDowncastMut, EnumVariantDowncastRef,
+    VariantIndex,
+};
+
+/// Filters and downcasts iterators of derived enums by variant token.
+///
+/// ```ignore
+/// let chats: Vec<&String> = events.iter().only::<ChatMessageVariant>().collect();
+/// let counts = events.iter().count_by_variant(); // indexed by `variant_index()`
+/// ```
+pub trait DTypeIterExt: Iterator + Sized {
+    /// Yields the `downcast_ref` targets of the variants of `Token`, from an
+    /// iterator of references.
+    fn only<Token>(self) -> Only<Self, Token> {
+        Only {
+            iter: self,
+            token: PhantomData,
+        }
+    }
+
+    /// Yields the `downcast_mut` targets of the variants of `Token`, from an
+    /// iterator of mutable references.
+    fn only_mut<Token>(self) -> OnlyMut<Self, Token> {
+        OnlyMut {
+            iter: self,
+            token: PhantomData,
+        }
+    }
+
+    /// Yields the `downcast` targets of the variants of `Token`, from an
+    /// iterator of owned values.
+    fn into_only<Token>(self) -> IntoOnly<Self, Token> {
+        IntoOnly {
+            iter: self,
+            token: PhantomData,
+        }
+    }
+
+    /// Counts the items per variant, indexed by `variant_index()`.
+    fn count_by_variant(self) -> Vec<usize>
+    where
+        Self::Item: VariantIndex,
+    {
+        let mut counts = vec![0; <Self::Item as VariantIndex>::VARIANT_COUNT];
+        for item in self {
+            counts[item.variant_index()] += 1;
+        }
+        counts
+    }
+}
+
+impl<I: Iterator> DTypeIterExt for I {}
+
+/// Iterator returned by [`DTypeIterExt::only`].
+pub struct Only<I, Token> {
+    iter: I,
+    token: PhantomData<fn() -> Token>,
+}
+
+impl<'a, I, E, Token> Iterator for Only<I, Token>
+where
+    I: Iterator<Item = &'a E>,
+    E: EnumVariantDowncastRef<Token> + ?Sized + 'a,
+{
+    type Item = E::Target<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.find_map(|item| item.downcast_ref())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+/// Iterator returned by [`DTypeIterExt::only_mut`].
+pub struct OnlyMut<I, Token> {
+    iter: I,
+    token: PhantomData<fn() -> Token>,
+}
+
+impl<'a, I, E, Token> Iterator for OnlyMut<I, Token>
+where
+    I: Iterator<Item = &'a mut E>,
+    E: EnumVariantDowncastMut<Token> + ?Sized + 'a,
+{
+    type Item = E::Target<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.find_map(|item| item.downcast_mut())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+/// Iterator returned by [`DTypeIterExt::into_only`].
+pub struct IntoOnly<I, Token> {
+    iter: I,
+    token: PhantomData<fn() -> Token>,
+}
+
+impl<I, Token> Iterator for IntoOnly<I, Token>
+where
+    I: Iterator,
+    I::Item: EnumVariantDowncast<Token>,
+{
+    type Item = <I::Item as EnumVariantDowncast<Token>>::Target;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.find_map(|item| item.downcast())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+// Manual impls, as derives would also bound `Token`, which is only a marker
+impl<I: Clone, Token> Clone for Only<I, Token> {
+    fn clone(&self) -> Self {
+        Only {
+            iter: self.iter.clone(),
+            token: PhantomData,
+        }
+    }
+}
+
+impl<I: fmt::Debug, Token> fmt::Debug for Only<I, Token> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Only").field("iter", &self.iter).finish()
+    }
+}
+
+impl<I: fmt::Debug, Token> fmt::Debug for OnlyMut<I, Token> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnlyMut").field("iter", &self.iter).finish()
+    }
+}
+
+impl<I: Clone, Token> Clone for IntoOnly<I, Token> {
+    fn clone(&self) -> Self {
+        IntoOnly {
+            iter: self.iter.clone(),
+            token: PhantomData,
+        }
+    }
+}
+
+impl<I: fmt::Debug, Token> fmt::Debug for IntoOnly<I, Token> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IntoOnly")
+            .field("iter", &self.iter)
+            .finish()
+    }
+}
@@ -1,6 +1,15 @@
 #![allow(clippy::approx_constant)]
 
-pub use dtype_variant_derive::{DType, build_dtype_tokens};
+pub use dtype_variant_derive::{
+    DType, DTypeFields, build_dtype_tokens, dtype_delegate,
+    dtype_delegate_impl, dtype_match,
+};
+
+mod iter;
+mod set;
+
+pub use iter::{DTypeIterExt, IntoOnly, Only, OnlyMut};
+pub use set::{VariantSet, VariantSetIter};
 
 pub trait EnumVariantDowncast<VariantToken> {
     type Target;
@@ -27,11 +36,179 @@ pub trait EnumVariantDowncastMut<VariantToken> {
     fn downcast_mut(&mut self) -> Option<Self::Target<'_>>;
 }
 
+pub trait EnumVariantConstruct<VariantToken> {
+    type Target;
+
+    /// Builds the enum as the variant corresponding to the token
+    fn construct(payload: Self::Target) -> Self;
+}
+
 // Define the EnumVariantConstraint trait with Constraint parameter
 pub trait EnumVariantConstraint<VariantToken> {
     type Constraint: 'static;
 }
 
+/// Names the payload types of the variant selected by `VariantToken`.
+pub trait VariantPayload<VariantToken> {
+    /// The payload as stored in the variant (e.g. `Vec<i32>`), the generated
+    /// `Fields` struct for struct variants, or `()` for unit variants.
+    type Full;
+    /// The payload with its container stripped (e.g. `i32`).
+    type Inner;
+    /// The container wrapping the payload, such that
+    /// `<Self::Container as PayloadContainer>::Wrap<Self::Inner>` is `Self::Full`.
+    type Container: PayloadContainer;
+}
+
+/// Maps a payload type `T` back to the token of the variant carrying it.
+///
+/// Implemented for the inner and full payload types of each variant, and for
+/// the generated `Fields` structs of struct variants. A type carried by
+/// several variants has an impl bounded on `UniquePayload`, so that using
+/// it reports the variants instead of picking one.
+#[diagnostic::on_unimplemented(
+    message = "`{T}` is not the payload type of exactly one variant of `{Self}`",
+    note = "payload types mentioning type parameters of the enum have no token"
+)]
+pub trait PayloadToken<T> {
+    type Token;
+}
+
+/// Never implemented: bounds the `PayloadToken` impl of a payload type `T`
+/// carried by several variants, whose tokens are the tuple `Variants`.
+#[doc(hidden)]
+#[diagnostic::on_unimplemented(
+    message = "`{T}` is the payload type of several variants of `{Self}`, with the tokens {Variants}",
+    label = "no unique token for `{T}`",
+    note = "name the token of the variant instead"
+)]
+pub trait UniquePayload<T, Variants> {}
+
+/// Identifies the variants of an enum by their position in declaration order.
+///
+/// Implemented by `#[derive(DType)]` next to the inherent `variant_index`, and
+/// for references to implementors.
+pub trait VariantIndex {
+    /// The number of variants of the enum.
+    const VARIANT_COUNT: usize;
+
+    /// Returns the position of the variant in declaration order.
+    fn variant_index(&self) -> usize;
+}
+
+impl<T: VariantIndex + ?Sized> VariantIndex for &T {
+    const VARIANT_COUNT: usize = T::VARIANT_COUNT;
+
+    fn variant_index(&self) -> usize {
+        T::variant_index(self)
+    }
+}
+
+impl<T: VariantIndex + ?Sized> VariantIndex for &mut T {
+    const VARIANT_COUNT: usize = T::VARIANT_COUNT;
+
+    fn variant_index(&self) -> usize {
+        T::variant_index(self)
+    }
+}
+
+/// The position of the variant of `VariantToken` in declaration order, as
+/// returned by `VariantIndex::variant_index` for its values.
+pub trait VariantTokenIndex<VariantToken>: VariantIndex {
+    const INDEX: usize;
+}
+
+/// The number of 64-bit words of a [`VariantSet`] of the enum, one per 64
+/// variants.
+///
+/// Implemented by `#[derive(DType)]` for the one count that fits the
+/// variants, which the sets of larger enums name as `VariantSet<E, WORDS>`.
+#[diagnostic::on_unimplemented(
+    message = "`VariantSet<{Self}, {WORDS}>` does not fit the variants of `{Self}`",
+    label = "not the word count of `{Self}`",
+    note = "a `VariantSet` holds 64 variants per word; name the words the variants of `{Self}` need, as in `VariantSet<{Self}, 2>` for up to 128"
+)]
+pub trait VariantWords<const WORDS: usize>: VariantIndex {}
+
+/// Names the token of the variant at `INDEX` in declaration order.
+///
+/// Implemented by `#[derive(DType)]` for the matchers, which reach the tokens
+/// through the enum type wherever it is in scope.
+#[doc(hidden)]
+pub trait VariantTokenAt<const INDEX: usize> {
+    type Token;
+}
+
+/// The bucket of the variant of `VariantToken` in the struct-of-arrays
+/// companion generated by `#[dtype(buckets = Name)]`.
+pub trait VariantBucket<VariantToken> {
+    /// The stored payload: the variant's payload, the `Fields` struct of a
+    /// struct variant, or `()` for a unit variant.
+    type Payload;
+
+    fn bucket(&self) -> &[Self::Payload];
+    fn bucket_mut(&mut self) -> &mut [Self::Payload];
+}
+
+/// The field indexed by `VariantToken` in a `#[derive(DTypeFields)]` struct.
+pub trait TokenField<VariantToken> {
+    type Field;
+
+    fn field(&self) -> &Self::Field;
+    fn field_mut(&mut self) -> &mut Self::Field;
+}
+
+/// Error of batch downcasts: the element at `index` holds another variant
+/// than the one requested, or than the first element for batch matchers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VariantMismatch {
+    /// Position of the first mismatching element.
+    pub index: usize,
+}
+
+impl std::fmt::Display for VariantMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "element {} does not hold the variant of the batch",
+            self.index
+        )
+    }
+}
+
+impl std::error::Error for VariantMismatch {}
+
+/// Downcasts every element of `items` to the variant of `Token`, failing on
+/// the first element holding another variant.
+///
+/// Derived enums also provide this as `Enum::downcast_all::<Token>(items)`.
+pub fn downcast_all<Token, E>(
+    items: &[E],
+) -> Result<Vec<E::Target<'_>>, VariantMismatch>
+where
+    E: EnumVariantDowncastRef<Token>,
+{
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            item.downcast_ref().ok_or(VariantMismatch { index })
+        })
+        .collect()
+}
+
+/// A type constructor wrapping variant payloads, such as `Vec`.
+pub trait PayloadContainer {
+    type Wrap<T>;
+}
+
+/// The container of payloads stored as-is.
+pub struct NoContainer;
+
+impl PayloadContainer for NoContainer {
+    type Wrap<T> = T;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -48,6 +225,7 @@ mod tests {
     #[dtype(
         matcher = match_my_enum_variant,
         shared_variant_zst_path = self,
+        payloads = MyEnum,
         constraint = Constraint
     )]
     pub enum MyEnumVariant {
@@ -102,6 +280,45 @@ mod tests {
         assert_eq!(my_enum, MyEnum::U16(vec![0]));
     }
 
+    #[derive(Clone, Debug, DType, PartialEq)]
+    #[dtype(constraint = "Copy + From<u8> + 'static")]
+    enum Reading {
+        Byte(u16),
+        Word(u64),
+        #[dtype(constraint = "Clone + Default")]
+        Note(String),
+        Absent,
+    }
+
+    fn constraint_name<Token>() -> &'static str
+    where
+        Reading: EnumVariantConstraint<Token>,
+    {
+        std::any::type_name::<
+            <Reading as EnumVariantConstraint<Token>>::Constraint,
+        >()
+    }
+
+    fn from_byte<Token>(
+        byte: u8,
+    ) -> <Reading as EnumVariantConstraint<Token>>::Constraint
+    where
+        Reading: EnumVariantConstraint<Token>,
+        <Reading as EnumVariantConstraint<Token>>::Constraint: From<u8>,
+    {
+        byte.into()
+    }
+
+    #[test]
+    fn test_rich_constraints() {
+        assert_eq!(from_byte::<ByteVariant>(7), 7_u16);
+        assert_eq!(from_byte::<WordVariant>(7), 7_u64);
+
+        // Per-variant overrides and the unit fallback
+        assert_eq!(constraint_name::<NoteVariant>(), "alloc::string::String");
+        assert_eq!(constraint_name::<AbsentVariant>(), "()");
+    }
+
     #[test]
     fn test_token_based_downcast() {
         let x = MyEnum::from(vec![1_u16, 1, 2, 3, 5]);
@@ -109,6 +326,573 @@ mod tests {
         assert_eq!(first_element, 1_u16);
     }
 
+    #[test]
+    fn test_variant_payload_types() {
+        fn type_name<T>() -> &'static str {
+            std::any::type_name::<T>()
+        }
+        type Payload<Token> = <MyEnum as VariantPayload<Token>>::Inner;
+        type Rewrapped<Token, T> = <<MyEnum as VariantPayload<Token>>::Container as PayloadContainer>::Wrap<T>;
+
+        let full: <MyEnum as VariantPayload<U32Variant>>::Full = vec![1];
+        let inner: Payload<U32Variant> = full[0];
+        assert_eq!(inner, 1_u32);
+        let rewrapped: Rewrapped<U32Variant, &str> = vec!["a"];
+        assert_eq!(rewrapped.len(), 1);
+
+        // Struct and unit variants are not wrapped in the container
+        assert_eq!(
+            type_name::<
+                <StructVariantData as VariantPayload<PersonVariant>>::Full,
+            >(),
+            type_name::<StructVariantDataPersonFields>()
+        );
+        assert_eq!(
+            type_name::<<MyEnumVariant as VariantPayload<U16Variant>>::Inner>(),
+            "()"
+        );
+        assert_eq!(
+            type_name::<<DynChunk as VariantPayload<I32Variant>>::Container>(),
+            type_name::<NoContainer>()
+        );
+    }
+
+    #[derive(DType)]
+    #[dtype(skip_from_impls)]
+    #[allow(dead_code)]
+    enum Heartbeat {
+        Ping(u32),
+        Pong(u32),
+        #[dtype(from)]
+        Payload(String),
+    }
+
+    #[test]
+    fn test_payload_token_lookup() {
+        // Both the inner and the full payload type map back to the token
+        assert!(matches!(MyEnumVariant::of::<u16>(), MyEnumVariant::U16));
+        assert!(matches!(MyEnumVariant::of::<Vec<u64>>(), MyEnumVariant::U64));
+        assert!(matches!(
+            MyEnumVariant::of_payload::<u32, MyEnum>(),
+            MyEnumVariant::U32
+        ));
+
+        let token: <StructVariantData as PayloadToken<
+            StructVariantDataPersonFields,
+        >>::Token = PersonVariant;
+        assert_eq!(
+            std::any::type_name_of_val(&token),
+            std::any::type_name::<PersonVariant>()
+        );
+
+        // `u32` is carried by two variants, so only `String` has a token
+        let _: <Heartbeat as PayloadToken<String>>::Token = PayloadVariant;
+    }
+
+    fn make<Token>(payload: u32) -> Heartbeat
+    where
+        Heartbeat: EnumVariantConstruct<Token, Target = u32>,
+    {
+        Heartbeat::construct(payload)
+    }
+
+    #[test]
+    fn test_token_driven_construction() {
+        // Variants sharing a payload type are told apart by their token
+        assert!(matches!(make::<PingVariant>(1), Heartbeat::Ping(1)));
+        assert!(matches!(make::<PongVariant>(2), Heartbeat::Pong(2)));
+
+        let person = StructVariantData::new::<PersonVariant>(
+            StructVariantDataPersonFields {
+                name: "Alice".to_string(),
+                age: 30,
+            },
+        );
+        assert_eq!(
+            person,
+            StructVariantData::Person {
+                name: "Alice".to_string(),
+                age: 30
+            }
+        );
+        assert!(matches!(
+            MyEnumVariant::new::<U32Variant>(()),
+            MyEnumVariant::U32
+        ));
+    }
+
+    #[test]
+    fn test_default_payloads() {
+        assert_eq!(MyEnum::default_of::<U16Variant>(), MyEnum::U16(vec![]));
+        assert_eq!(
+            StructVariantData::default_of::<ScoreVariant>(),
+            StructVariantData::Score(0)
+        );
+
+        // Build an empty column from a kind at runtime
+        let kind = MyEnumVariant::U32;
+        assert_eq!(kind.variant_index(), 1);
+        assert_eq!(
+            MyEnum::default_for_index(kind.variant_index()),
+            Some(MyEnum::U32(vec![]))
+        );
+        assert_eq!(
+            StructVariantData::default_for_index(0),
+            Some(StructVariantData::Person {
+                name: String::new(),
+                age: 0
+            })
+        );
+        assert_eq!(MyEnum::default_for_index(3), None);
+    }
+
+    #[derive(DType, Debug, PartialEq)]
+    enum Signal {
+        Level(u8),
+        #[dtype(no_from)]
+        Pulse(u8),
+    }
+
+    #[test]
+    fn test_per_variant_from_control() {
+        assert_eq!(Signal::from(3_u8), Signal::Level(3));
+        assert_eq!(Signal::new::<PulseVariant>(3), Signal::Pulse(3));
+
+        // `from` opts a variant back in despite `skip_from_impls`
+        assert!(matches!(
+            Heartbeat::from("beat".to_string()),
+            Heartbeat::Payload(payload) if payload == "beat"
+        ));
+    }
+
+    mod facade {
+        pub use crate as dtype_variant;
+    }
+
+    #[derive(DType, Debug, PartialEq)]
+    #[dtype(crate = crate::tests::facade::dtype_variant)]
+    enum Reexported {
+        Circle(f64),
+        Square { side: f64 },
+    }
+
+    #[test]
+    fn test_crate_path_override() {
+        let circle = Reexported::new::<CircleVariant>(1.5);
+        assert_eq!(circle.downcast_ref::<CircleVariant>(), Some(&1.5));
+        let square = Reexported::from(ReexportedSquareFields { side: 2.0 });
+        assert_eq!(*square.downcast_ref::<SquareVariant>().unwrap().side, 2.0);
+    }
+
+    #[derive(DType, Debug, PartialEq)]
+    #[dtype(matcher = match_legacy, forward_attrs(allow))]
+    enum Legacy {
+        /// Current wire encoding.
+        Current(u32),
+        #[deprecated = "use `Current`"]
+        Old {
+            /// Raw value from the old wire format.
+            #[allow(unused)]
+            raw: u16,
+        },
+        #[cfg(any())]
+        Disabled(String),
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_forwarded_variant_attributes() {
+        let current = Legacy::from(7_u32);
+        let old = Legacy::Old { raw: 3 };
+        let widen = |value: &Legacy| {
+            match_legacy!(value, {
+                Legacy::Current(v) => *v,
+                Legacy::Old { raw } => u32::from(*raw),
+            })
+        };
+        assert_eq!(widen(&current), 7);
+        assert_eq!(widen(&old), 3);
+        assert_eq!(*old.downcast_ref::<OldVariant>().unwrap().raw, 3);
+        assert_eq!(old.variant_index(), 1);
+    }
+
+    mod delegation {
+        use crate::{DType, dtype_delegate};
+        use std::fmt::Display;
+
+        #[dtype_delegate]
+        pub trait Gauge {
+            fn read(&self) -> f64;
+            fn scale(&mut self, factor: f64);
+            fn into_reading(self) -> String
+            where
+                Self: Sized;
+            fn doubled(&self) -> f64 {
+                self.read() * 2.0
+            }
+            fn unit() -> &'static str
+            where
+                Self: Sized,
+            {
+                ""
+            }
+        }
+
+        impl Gauge for f64 {
+            fn read(&self) -> f64 {
+                *self
+            }
+            fn scale(&mut self, factor: f64) {
+                *self *= factor;
+            }
+            fn into_reading(self) -> String {
+                format!("{self:.1}")
+            }
+        }
+
+        impl Gauge for u8 {
+            fn read(&self) -> f64 {
+                f64::from(*self)
+            }
+            fn scale(&mut self, factor: f64) {
+                *self = (f64::from(*self) * factor) as u8;
+            }
+            fn into_reading(self) -> String {
+                format!("{self}%")
+            }
+            fn doubled(&self) -> f64 {
+                f64::from(self.saturating_mul(2))
+            }
+        }
+
+        pub(crate) type Label = String;
+
+        #[dtype_delegate]
+        pub(crate) trait Labeled {
+            fn label(&self) -> crate::tests::delegation::Label;
+        }
+
+        impl Labeled for f64 {
+            fn label(&self) -> Label {
+                "celsius".to_string()
+            }
+        }
+
+        impl Labeled for u8 {
+            fn label(&self) -> Label {
+                "percent".to_string()
+            }
+        }
+
+        #[derive(DType, Debug, PartialEq)]
+        #[dtype(delegate(Display, Gauge, Labeled))]
+        pub enum Sensor {
+            Celsius(f64),
+            Percent(u8),
+        }
+
+        #[derive(DType)]
+        #[dtype(skip_from_impls, delegate(Gauge, std::fmt::Debug))]
+        pub enum Tagged<T> {
+            Value(T),
+            Fixed(u8),
+        }
+    }
+
+    #[test]
+    fn test_delegated_traits() {
+        use delegation::{Gauge, Labeled, Sensor, Tagged};
+
+        let mut sensor = Sensor::from(21.5_f64);
+        sensor.scale(2.0);
+        assert_eq!(sensor.read(), 43.0);
+        assert_eq!(sensor.doubled(), 86.0);
+        assert_eq!(sensor.to_string(), "43");
+        assert_eq!(<Sensor as Gauge>::unit(), "");
+        assert_eq!(sensor.label(), "celsius");
+
+        let percent = Sensor::from(200_u8);
+        assert_eq!(percent.doubled(), 255.0);
+        assert_eq!(percent.into_reading(), "200%");
+
+        let tagged = Tagged::<f64>::Value(1.25);
+        assert_eq!(tagged.read(), 1.25);
+        assert_eq!(format!("{:?}", Tagged::<f64>::Fixed(3)), "3");
+    }
+
+    #[derive(DType, Debug, PartialEq)]
+    #[dtype(accessors, buckets = RequestBuckets)]
+    enum Request {
+        HTTPGet(String),
+        PlayerMove { player_id: u32, x: f32 },
+        Shutdown,
+    }
+
+    #[test]
+    fn test_named_accessors() {
+        let mut get = Request::HTTPGet("/".to_string());
+        assert!(get.is_http_get());
+        assert!(!get.is_shutdown());
+        get.as_http_get_mut().unwrap().push_str("index");
+        assert_eq!(get.as_http_get(), Some(&"/index".to_string()));
+        assert!(get.as_player_move().is_none());
+        assert_eq!(get.into_http_get(), Some("/index".to_string()));
+
+        let mut moved = Request::PlayerMove {
+            player_id: 7,
+            x: 1.0,
+        };
+        *moved.as_player_move_mut().unwrap().x += 1.5;
+        assert_eq!(*moved.as_player_move().unwrap().x, 2.5);
+        assert_eq!(
+            moved.into_player_move(),
+            Some(RequestPlayerMoveFields {
+                player_id: 7,
+                x: 2.5
+            })
+        );
+        assert!(Request::Shutdown.is_shutdown());
+    }
+
+    #[test]
+    fn test_iterator_adapters() {
+        let mut requests = vec![
+            Request::HTTPGet("/a".to_string()),
+            Request::Shutdown,
+            Request::PlayerMove {
+                player_id: 1,
+                x: 0.5,
+            },
+            Request::HTTPGet("/b".to_string()),
+        ];
+
+        let paths: Vec<&String> =
+            requests.iter().only::<HTTPGetVariant>().collect();
+        assert_eq!(paths, ["/a", "/b"]);
+        let mut only_paths = requests.iter().only::<HTTPGetVariant>();
+        only_paths.next();
+        assert_eq!(only_paths.clone().collect::<Vec<_>>(), ["/b"]);
+        assert!(format!("{:?}", only_paths).starts_with("Only"));
+        for moved in requests.iter_mut().only_mut::<PlayerMoveVariant>() {
+            *moved.x *= 4.0;
+        }
+        assert_eq!(requests.iter().count_by_variant(), [2, 1, 1]);
+        assert_eq!(
+            requests.into_iter().into_only::<PlayerMoveVariant>().next(),
+            Some(RequestPlayerMoveFields {
+                player_id: 1,
+                x: 2.0
+            })
+        );
+    }
+
+    #[test]
+    fn test_variant_buckets() {
+        let requests = vec![
+            Request::HTTPGet("/a".to_string()),
+            Request::Shutdown,
+            Request::PlayerMove {
+                player_id: 1,
+                x: 0.5,
+            },
+            Request::HTTPGet("/b".to_string()),
+        ];
+
+        let mut buckets = RequestBuckets::from(requests);
+        assert_eq!(buckets.get::<HTTPGetVariant>(), ["/a", "/b"]);
+        assert_eq!(buckets.len_of::<ShutdownVariant>(), 1);
+        assert_eq!(buckets.variant_lens(), [2, 1, 1]);
+        assert_eq!(buckets.len(), 4);
+        buckets.get_mut::<PlayerMoveVariant>()[0].x = 2.0;
+        buckets.push(Request::Shutdown);
+
+        let expected = vec![
+            Request::HTTPGet("/a".to_string()),
+            Request::Shutdown,
+            Request::PlayerMove {
+                player_id: 1,
+                x: 2.0,
+            },
+            Request::HTTPGet("/b".to_string()),
+            Request::Shutdown,
+        ];
+        assert_eq!(buckets.iter_ordered().collect::<Vec<_>>(), expected);
+        assert_eq!(Vec::from(buckets), expected);
+
+        let mut unordered = RequestBuckets::unordered();
+        unordered.extend(expected);
+        assert!(!unordered.is_ordered());
+        let variants: Vec<usize> = unordered
+            .into_ordered()
+            .map(|r| r.variant_index())
+            .collect();
+        assert_eq!(variants, [0, 0, 1, 2, 2]);
+    }
+
+    #[test]
+    fn test_batch_downcasts() {
+        let chunks =
+            vec![MyEnum::from(vec![1_u16]), MyEnum::from(vec![2_u16, 3])];
+        let all = MyEnum::downcast_all::<U16Variant>(&chunks).unwrap();
+        assert_eq!(all, [&vec![1], &vec![2, 3]]);
+        assert_eq!(
+            MyEnum::downcast_all::<U32Variant>(&chunks),
+            Err(VariantMismatch { index: 0 })
+        );
+
+        let concat = |chunks: &[MyEnum]| {
+            match_my_enum!(all chunks, MyEnum<T, Token>(inputs) => {
+                let inputs: Vec<TRef<'_>> = inputs;
+                MyEnum::new::<Token>(inputs.into_iter().flatten().copied().collect())
+            })
+        };
+        assert_eq!(concat(&chunks), Ok(Some(MyEnum::from(vec![1_u16, 2, 3]))));
+        assert_eq!(concat(&[]), Ok(None));
+        let mixed = [MyEnum::from(vec![1_u16]), MyEnum::from(vec![2_u32])];
+        assert_eq!(concat(&mixed), Err(VariantMismatch { index: 1 }));
+    }
+
+    #[test]
+    fn test_variant_transitions() {
+        let mut value = MyEnum::from(vec![1_u16, 2]);
+        let previous = value.set::<U32Variant>(vec![5_u32]);
+        assert!(matches!(previous, MyEnum::U16(ref v) if v == &[1, 2]));
+        assert_eq!(value.downcast_ref::<U32Variant>(), Some(&vec![5]));
+
+        assert_eq!(value.take_as::<U16Variant>(), None);
+        assert_eq!(value.take_as::<U32Variant>(), Some(vec![5]));
+        assert_eq!(value.downcast_ref::<U32Variant>(), Some(&vec![]));
+
+        let doubled =
+            MyEnum::from(vec![3_u16]).map_variant::<U16Variant, _>(|v| {
+                v.iter().map(|x| x * 2).collect()
+            });
+        assert_eq!(doubled.downcast_ref::<U16Variant>(), Some(&vec![6]));
+        let untouched =
+            MyEnum::from(vec![15_u32]).map_variant::<U16Variant, _>(|_| vec![]);
+        assert_eq!(untouched.downcast_ref::<U32Variant>(), Some(&vec![15]));
+    }
+
+    #[test]
+    fn test_explicit_binding_modes() {
+        let mut x = MyEnum::from(vec![1_u16, 2, 3]);
+
+        // `ref` binds a shared reference and names it through `TRef<'_>`
+        let len = match_my_enum!(ref x, MyEnum<T, Token>(inner) => {
+            let inner: TRef<'_> = inner;
+            inner.len()
+        });
+        assert_eq!(len, 3);
+
+        // `mut` binds a mutable reference, even on an owned place
+        match_my_enum!(mut x, MyEnum<T, Token>(inner) => {
+            let inner: TMut<'_> = inner;
+            inner.push(T::default());
+        });
+        assert_eq!(x, MyEnum::U16(vec![1, 2, 3, 0]));
+
+        // `move` binds the owned payload
+        let owned = match_my_enum!(move x, MyEnum<T, Token>(inner) => {
+            let inner: TOwned = inner;
+            inner.len()
+        });
+        assert_eq!(owned, 4);
+    }
+
+    #[test]
+    fn test_matcher_flexible_grammar() {
+        let x = MyEnum::from(vec![1_u16, 2, 3]);
+
+        // Non-block bodies, where clauses and a trailing comma are accepted
+        let bits = match_my_enum!(
+            &x,
+            MyEnum<T, Token>(inner) where T: Copy + Into<u64> => inner.len() as u64 * T::BITS as u64,
+        );
+        assert_eq!(bits, 48);
+
+        let kind = match_my_enum_variant!(
+            MyEnumVariant::U32,
+            MyEnumVariant<Token>, MyEnum<Dest, Constraint> => Dest::default().len(),
+        );
+        assert_eq!(kind, 0);
+    }
+
+    mod scalars {
+        use super::*;
+
+        #[derive(Clone, Debug, DType, PartialEq)]
+        #[dtype(shared_variant_zst_path = super)]
+        pub enum Scalar {
+            U16(u16),
+            U32(u32),
+            U64(u64),
+        }
+
+        #[derive(Clone, Debug, DType, PartialEq)]
+        #[dtype(shared_variant_zst_path = super)]
+        pub enum Tagged<M> {
+            U16((u16, M)),
+            U32((u32, M)),
+            U64((u64, M)),
+        }
+    }
+
+    #[test]
+    fn test_matcher_multiple_dest_enums() {
+        let (chunk, scalar) = match_my_enum_variant!(
+            MyEnumVariant::U32,
+            crate::tests::MyEnumVariant<Token>,
+            MyEnum<Chunk, C>,
+            scalars::Scalar<Value> => {
+                (MyEnum::from(Chunk::default()), scalars::Scalar::from(Value::default()))
+            }
+        );
+        assert_eq!(chunk, MyEnum::U32(vec![]));
+        assert_eq!(scalar, scalars::Scalar::U32(0));
+
+        // Explicit generic arguments precede the alias list
+        let tagged = match_my_enum_variant!(
+            MyEnumVariant::U64,
+            self::MyEnumVariant<Token>,
+            scalars::Tagged<&'static str><Payload> => {
+                let payload: Payload = Default::default();
+                scalars::Tagged::from(payload)
+            }
+        );
+        assert_eq!(tagged, scalars::Tagged::U64((0, "")));
+    }
+
+    #[derive(Debug, Default, DTypeFields)]
+    #[dtype(shared_variant_zst_path = self, kind = MyEnumVariant)]
+    struct Columns {
+        u16: Vec<u16>,
+        u32: Vec<u32>,
+        #[dtype(variant = U64)]
+        wide: Vec<u64>,
+    }
+
+    #[test]
+    fn test_token_indexed_fields() {
+        let mut columns = Columns::default();
+        columns.get_mut::<U16Variant>().push(1);
+        columns.get_mut::<U64Variant>().extend([2, 3]);
+        assert_eq!(columns.get::<U16Variant>(), &[1]);
+        assert_eq!(columns.wide, [2, 3]);
+
+        let len_of = |columns: &Columns, kind: MyEnumVariant| {
+            match_my_enum_variant!(kind, MyEnumVariant<Token> => {
+                columns.get::<Token>().len()
+            })
+        };
+        assert_eq!(len_of(&columns, MyEnumVariant::U64), 2);
+        assert_eq!(len_of(&columns, MyEnumVariant::U32), 0);
+
+        let field = columns.get_by_kind_mut(&MyEnumVariant::U32).unwrap();
+        field.downcast_mut::<Vec<u32>>().unwrap().push(4);
+        let field = columns.get_by_kind(&MyEnumVariant::U32).unwrap();
+        assert_eq!(field.downcast_ref::<Vec<u32>>(), Some(&vec![4]));
+    }
+
     build_dtype_tokens!([I32, F32]);
 
     #[derive(Clone, Debug, DType)]
@@ -218,6 +1002,165 @@ mod tests {
         assert_eq!(size_str, "Large");
     }
 
+    #[test]
+    fn test_variant_sets() {
+        const NUMERIC: VariantSet<MyData> =
+            match_by_category!(set MyData(Numeric));
+        const FLOAT_OR_STR: VariantSet<MyData> =
+            VariantSet::of::<FloatVariant>().with::<StrVariant>();
+
+        assert!(NUMERIC.contains(&MyData::Int(1)));
+        assert!(!NUMERIC.contains(&MyData::Str("a".to_string())));
+        assert_eq!(NUMERIC.iter().collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(NUMERIC & FLOAT_OR_STR, VariantSet::of::<FloatVariant>());
+        assert_eq!(NUMERIC | FLOAT_OR_STR, VariantSet::all());
+        assert_eq!(!NUMERIC, match_by_size!(set MyData(Str)));
+        assert!(NUMERIC.is_superset(&match_by_size!(set MyData(Small))));
+
+        let mut set = match_by_category!(set MyData(Text | Int));
+        assert!(!set.insert::<StrVariant>());
+        assert!(set.remove::<IntVariant>());
+        assert_eq!(set, VariantSet::of::<StrVariant>());
+        assert_eq!(format!("{:?}", set), "{2}");
+    }
+
+    mod wide {
+        use super::*;
+
+        #[derive(DType)]
+        #[dtype(matcher = match_wide)]
+        #[allow(dead_code)]
+        pub enum Wide {
+            V0,
+            V1,
+            V2,
+            V3,
+            V4,
+            V5,
+            V6,
+            V7,
+            V8,
+            V9,
+            V10,
+            V11,
+            V12,
+            V13,
+            V14,
+            V15,
+            V16,
+            V17,
+            V18,
+            V19,
+            V20,
+            V21,
+            V22,
+            V23,
+            V24,
+            V25,
+            V26,
+            V27,
+            V28,
+            V29,
+            V30,
+            V31,
+            V32,
+            V33,
+            V34,
+            V35,
+            V36,
+            V37,
+            V38,
+            V39,
+            V40,
+            V41,
+            V42,
+            V43,
+            V44,
+            V45,
+            V46,
+            V47,
+            V48,
+            V49,
+            V50,
+            V51,
+            V52,
+            V53,
+            V54,
+            V55,
+            V56,
+            V57,
+            V58,
+            V59,
+            V60,
+            V61,
+            V62,
+            V63,
+            V64,
+            V65,
+        }
+    }
+
+    #[test]
+    fn test_wide_variant_sets() {
+        use wide::*;
+
+        const EDGES: VariantSet<Wide, 2> =
+            wide::match_wide!(set Wide(V0 | V65));
+        let mut set = VariantSet::<Wide, 2>::of::<V64Variant>();
+        assert!(set.insert::<V65Variant>());
+        assert!(set.contains(&Wide::V65) && set.contains_index(64));
+        assert!(!set.contains_index(66));
+        assert_eq!(set.iter().collect::<Vec<_>>(), [64, 65]);
+        assert_eq!((set | EDGES).len(), 3);
+        assert_eq!(VariantSet::<Wide, 2>::all().len(), 66);
+        assert_eq!((!EDGES).iter().len(), 64);
+    }
+
+    #[test]
+    fn test_partial_matcher() {
+        let describe = |data: &MyData| {
+            match_by_category!(data, {
+                Str(s) => format!("string of length {}", s.len()),
+                _: MyData<T, Variant>(inner) => format!("{}: {}", std::any::type_name::<T>(), inner),
+            })
+        };
+        assert_eq!(
+            describe(&MyData::Str("hi".to_string())),
+            "string of length 2"
+        );
+        assert_eq!(describe(&MyData::Int(7)), "i32: 7");
+        assert_eq!(describe(&MyData::Float(0.5)), "f64: 0.5");
+
+        // Guarded variant arms fall back to the generic arm
+        let x = DynChunk::from(-3_i32);
+        let abs = match_dyn_enum!(x, {
+            I32(v) if v < 0 => -v as f64,
+            _: DynChunk<T, Token>(v) => v as f64,
+        });
+        assert_eq!(abs, 3.0);
+    }
+
+    #[test]
+    fn test_grouped_matcher_with_variant_arms() {
+        let size = |data: MyData| {
+            match_by_size!(data, {
+                Float(f) => f as usize,
+                Small: MyData<T, Variant>(inner) => { inner as usize }
+                Large: MyData<T, Variant>(inner) => { inner.len() }
+            })
+        };
+        assert_eq!(size(MyData::Int(4)), 4);
+        assert_eq!(size(MyData::Float(2.0)), 2);
+        assert_eq!(size(MyData::Str("abc".to_string())), 3);
+
+        // `_` covers the groups without an arm of their own
+        let is_text = match_by_category!(MyData::Int(1), {
+            Text: MyData<Variant> => true,
+            _: MyData<Variant> => false,
+        });
+        assert!(!is_text);
+    }
+
     build_dtype_tokens!([Person, Location, Score]); // Add tokens for struct variant test
 
     #[derive(DType, Debug, Clone, PartialEq)]
@@ -229,6 +1172,89 @@ mod tests {
         Score(i32),
     }
 
+    #[derive(Clone)]
+    struct Opaque(u8);
+
+    #[derive(DType)]
+    #[dtype(
+        shared_variant_zst_path = self,
+        fields_derive(Debug, Clone, PartialEq, Eq, Hash),
+        ref_derive(Debug, Copy, Clone),
+        mut_derive(Debug)
+    )]
+    #[allow(dead_code)]
+    pub(crate) enum ConfiguredStructs {
+        Person { name: String, age: u32 },
+        Location { lat: u64, lng: u64 },
+        Score(i32),
+    }
+
+    #[derive(DType)]
+    #[dtype(fields_derive(Clone))]
+    #[allow(dead_code)]
+    enum OpaquePayload {
+        Blob { data: Opaque },
+    }
+
+    #[test]
+    fn test_generated_struct_derives() {
+        let person = ConfiguredStructs::Person {
+            name: "Alice".to_string(),
+            age: 30,
+        };
+
+        // Copy + Debug on the Ref struct
+        let person_ref = person.downcast_ref::<PersonVariant>().unwrap();
+        let copied = person_ref;
+        assert_eq!(
+            format!("{:?}", copied),
+            "ConfiguredStructsPersonRef { name: \"Alice\", age: 30 }"
+        );
+
+        // Hash + Eq on the Fields struct
+        let fields: ConfiguredStructsPersonFields = person_ref.into();
+        let set: std::collections::HashSet<_> = [fields.clone(), fields].into();
+        assert_eq!(set.len(), 1);
+
+        // Payloads only need to satisfy the configured derives
+        let blob = OpaquePayload::Blob { data: Opaque(7) };
+        let fields = blob.downcast::<BlobVariant>().unwrap();
+        assert_eq!(fields.clone().data.0, 7);
+    }
+
+    #[derive(DType)]
+    #[dtype(fields_derive())]
+    enum Resource {
+        Handle {
+            name: String,
+            sink: Box<dyn std::io::Write>,
+        },
+        Named {
+            name: String,
+        },
+    }
+
+    #[test]
+    fn test_non_clone_struct_fields() {
+        let mut resource = Resource::Handle {
+            name: "log".to_string(),
+            sink: Box::new(Vec::new()),
+        };
+        let handle = resource.downcast_mut::<HandleVariant>().unwrap();
+        assert_eq!(handle.name, "log");
+        assert!(handle.sink.write_all(b"entry").is_ok());
+
+        // Owned conversions stay available for variants whose fields clone
+        let named = Resource::Named {
+            name: "config".to_string(),
+        };
+        let named_ref = named.downcast_ref::<NamedVariant>().unwrap();
+        let fields = named_ref.to_owned_fields();
+        assert_eq!(fields.name, "config");
+        let fields: ResourceNamedFields = named_ref.into();
+        assert_eq!(fields.name, "config");
+    }
+
     #[test]
     fn test_struct_variants() {
         // Test struct variant creation
@@ -343,6 +1369,36 @@ mod tests {
         assert_eq!(person_result, "Reference to person variant");
     }
 
+    #[test]
+    fn test_struct_variant_matcher_binding_modes() {
+        let mut person_data = StructVariantData::Person {
+            name: "Alice".to_string(),
+            age: 30,
+        };
+
+        // Struct variants are bound as their generated Ref/Mut/Fields types
+        let ref_type = match_struct_variant_data!(ref person_data, StructVariantData<S, Token>(inner) => {
+            let _: SRef<'_> = inner;
+            std::any::type_name::<SRef<'_>>()
+        });
+        assert!(ref_type.ends_with("StructVariantDataPersonRef"));
+
+        let mut_type = match_struct_variant_data!(mut person_data, StructVariantData<S, Token>(inner) => {
+            let _: SMut<'_> = inner;
+            std::any::type_name::<SMut<'_>>()
+        });
+        assert!(mut_type.ends_with("StructVariantDataPersonMut"));
+
+        let fields = match_struct_variant_data!(move person_data, StructVariantData<S, Token>(inner) => {
+            let inner: SOwned = inner;
+            format!("{:?}", inner)
+        });
+        assert_eq!(
+            fields,
+            "StructVariantDataPersonFields { name: \"Alice\", age: 30 }"
+        );
+    }
+
     #[test]
     fn test_struct_variant_reference_downcasting() {
         let person_data = StructVariantData::Person {
@@ -555,4 +1611,351 @@ mod tests {
         assert!(person.downcast_ref::<LocationVariant>().is_none());
         assert!(person.downcast_ref::<ScoreVariant>().is_none());
     }
+
+    mod borrowed {
+        use super::*;
+
+        #[derive(Debug, PartialEq)]
+        pub struct Opaque(pub u8);
+
+        #[derive(DType, Debug)]
+        #[dtype(matcher = match_event, ref_derive(Debug))]
+        pub enum Event<'a, P>
+        where
+            P: std::fmt::Debug,
+        {
+            Message { text: &'a str, payload: P },
+            Tag { label: &'a str },
+            Range { start: usize, end: usize },
+            Count(u32),
+        }
+
+        #[test]
+        fn test_generic_struct_variants() {
+            let text = String::from("hello");
+            let mut event = Event::Message {
+                text: &text,
+                payload: vec![1_u8, 2],
+            };
+
+            let message = event.downcast_ref::<MessageVariant>().unwrap();
+            assert_eq!(*message.text, "hello");
+            let fields: EventMessageFields<'_, Vec<u8>> = message.into();
+            assert_eq!(fields.payload, vec![1, 2]);
+
+            event
+                .downcast_mut::<MessageVariant>()
+                .unwrap()
+                .payload
+                .push(3);
+            let debug = match_event!(ref event, Event<Token>(inner) => format!("{:?}", inner));
+            assert_eq!(
+                debug,
+                "EventMessageRef { text: \"hello\", payload: [1, 2, 3] }"
+            );
+
+            // Only the generics a variant uses end up on its structs
+            let tag_event: Event<'_, Opaque> = Event::Tag { label: &text };
+            let tag: EventTagFields<'_> =
+                tag_event.downcast::<TagVariant>().unwrap();
+            assert_eq!(tag.label, "hello");
+            let range = EventRangeFields { start: 1, end: 4 };
+            assert!(matches!(
+                Event::<'_, Opaque>::from(range),
+                Event::Range { start: 1, end: 4 }
+            ));
+
+            // Owned downcasting moves the fields, so no bounds are needed
+            let message = Event::Message {
+                text: &text,
+                payload: Opaque(7),
+            }
+            .downcast::<MessageVariant>()
+            .unwrap();
+            assert_eq!(message.payload, Opaque(7));
+        }
+
+        #[derive(DType, Debug)]
+        #[dtype(matcher = match_ev, ref_derive(Debug), mut_derive(Debug))]
+        pub enum Ev<'a> {
+            Msg { text: &'a str },
+            Tally(u32),
+        }
+
+        fn describe(ev: &Ev<'_>) -> String {
+            match_ev!(ref *ev, Ev<Token>(inner) => format!("{:?}", inner))
+        }
+
+        fn bump(ev: &mut Ev<'_>) -> String {
+            match_ev!(mut *ev, Ev<Token>(inner) => format!("{:?}", inner))
+        }
+
+        fn take(ev: Ev<'_>) -> String {
+            match_ev!(move ev, Ev<Token>(inner) => format!("{:?}", inner))
+        }
+
+        fn payload_name(ev: &Ev<'_>) -> &'static str {
+            match_ev!(ev, Ev<Src, Token>(_inner) => std::any::type_name::<Src>())
+        }
+
+        #[test]
+        fn test_lifetime_only_matchers() {
+            let text = String::from("hi");
+            let mut msg = Ev::Msg { text: &text };
+            assert_eq!(describe(&msg), "EvMsgRef { text: \"hi\" }");
+            assert_eq!(bump(&mut msg), "EvMsgMut { text: \"hi\" }");
+            assert!(payload_name(&msg).ends_with("EvMsgFields"));
+            assert_eq!(payload_name(&Ev::Tally(1)), "u32");
+            assert_eq!(take(Ev::Tally(2)), "2");
+            assert_eq!(take(msg), "EvMsgFields { text: \"hi\" }");
+        }
+    }
+
+    // Identical items in sibling modules, whose exported macros share the
+    // crate root
+    mod twin_a {
+        use crate::{DType, dtype_delegate};
+
+        #[dtype_delegate]
+        pub trait Twin {
+            fn twin(&self) -> u8;
+        }
+
+        impl Twin for u8 {
+            fn twin(&self) -> u8 {
+                *self
+            }
+        }
+
+        #[derive(DType)]
+        #[dtype(matcher = match_twin, delegate(Twin))]
+        pub enum Pair {
+            One(u8),
+        }
+    }
+
+    mod twin_b {
+        use crate::{DType, dtype_delegate};
+
+        #[dtype_delegate]
+        pub trait Twin {
+            fn twin(&self) -> u8;
+        }
+
+        impl Twin for u8 {
+            fn twin(&self) -> u8 {
+                *self
+            }
+        }
+
+        #[derive(DType)]
+        #[dtype(matcher = match_twin, delegate(Twin))]
+        pub enum Pair {
+            One(u8),
+        }
+    }
+
+    #[test]
+    fn test_identical_items_in_sibling_modules() {
+        use twin_a::Twin as _;
+        use twin_b::Twin as _;
+
+        let a = twin_a::Pair::One(1);
+        let b = twin_b::Pair::One(2);
+        assert_eq!(
+            twin_a::match_twin!(ref a, twin_a::Pair<Token>(inner) => *inner),
+            1
+        );
+        assert_eq!(
+            twin_b::match_twin!(ref b, twin_b::Pair<Token>(inner) => *inner),
+            2
+        );
+        assert_eq!((a.twin(), b.twin()), (1, 2));
+    }
+
+    mod shapes {
+        use super::*;
+
+        #[derive(DType, Debug)]
+        #[dtype(matcher = match_shape)]
+        pub enum Shape {
+            Circle(f64),
+            Rect { w: f64, h: f64 },
+            Empty,
+        }
+
+        /// Shares its name and matcher name with `borrowed::Event`.
+        #[derive(DType, Debug)]
+        #[dtype(matcher = match_event, matcher_vis = "pub(crate)")]
+        pub enum Event {
+            Tick(u32),
+            Idle,
+        }
+    }
+
+    #[test]
+    fn test_matchers_across_modules() {
+        let shapes = [
+            shapes::Shape::Circle(1.0),
+            shapes::Shape::Rect { w: 2.0, h: 3.0 },
+            shapes::Shape::Empty,
+        ];
+        let areas: Vec<f64> = shapes
+            .iter()
+            .map(|shape| {
+                shapes::match_shape!(shape, {
+                    Circle(r) => 3.0 * r * r,
+                    Rect { w, h } => w * h,
+                    _: shapes::Shape<Token> => 0.0,
+                })
+            })
+            .collect();
+        assert_eq!(areas, [3.0, 6.0, 0.0]);
+
+        // Payload aliases resolve through the enum, wherever it is named from
+        let widths: Vec<f64> = shapes
+            .iter()
+            .map(|shape| {
+                shapes::match_shape!(ref shape, {
+                    shapes::Shape::Circle(r) => 2.0 * r,
+                    _: shapes::Shape<Src, Token>(inner) => {
+                        let fields: Option<SrcRef<'_>> = Some(inner);
+                        fields.map_or(0.0, |fields| {
+                            std::mem::size_of_val(&fields) as f64
+                        })
+                    },
+                })
+            })
+            .collect();
+        let ref_size = std::mem::size_of::<&f64>() as f64;
+        assert_eq!(widths, [2.0, 2.0 * ref_size, 0.0]);
+
+        // Tokens and structs resolve through the enum's name as imported
+        use shapes::Shape;
+        let tokens: Vec<&str> = shapes
+            .iter()
+            .map(|shape| {
+                shapes::match_shape!(ref shape, Shape<Token>(_inner) => {
+                    std::any::type_name::<Token>()
+                })
+            })
+            .collect();
+        assert!(tokens[0].ends_with("CircleVariant"));
+        assert!(tokens[1].ends_with("RectVariant"));
+        assert!(tokens[2].ends_with("EmptyVariant"));
+        let kinds: Vec<&str> = shapes
+            .iter()
+            .map(|shape| {
+                shapes::match_shape!(shape, {
+                    Circle(_) => "circle",
+                    _: Shape<Token> => std::any::type_name::<Token>(),
+                })
+            })
+            .collect();
+        assert_eq!(kinds[0], "circle");
+        assert!(kinds[1].ends_with("RectVariant"));
+        assert!(kinds[2].ends_with("EmptyVariant"));
+        let round = shapes::match_shape!(set Shape(Circle | Empty));
+        assert!(round.contains(&shapes[0]) && !round.contains(&shapes[1]));
+        let owned: Vec<String> = shapes
+            .into_iter()
+            .map(|shape| {
+                shapes::match_shape!(move shape, Shape<Src, Token>(inner) => {
+                    format!("{:?}", inner)
+                })
+            })
+            .collect();
+        assert_eq!(owned, ["1.0", "ShapeRectFields { w: 2.0, h: 3.0 }", "()"]);
+
+        let tick = shapes::Event::Tick(3);
+        let ticks = shapes::match_event!(tick, {
+            shapes::Event::Tick(n) => n,
+            Idle => 0,
+        });
+        assert_eq!(ticks, 3);
+        let text = String::from("hi");
+        let tag: borrowed::Event<'_, u8> =
+            borrowed::Event::Tag { label: &text };
+        let label = borrowed::match_event!(ref tag, borrowed::Event<Token>(inner) => {
+            format!("{:?}", inner)
+        });
+        assert_eq!(label, "EventTagRef { label: \"hi\" }");
+    }
+
+    mod remote {
+        use super::*;
+
+        /// Stands in for an enum defined in another crate.
+        pub mod proto {
+            #[derive(Debug, PartialEq)]
+            pub enum Message {
+                Ping(u32),
+                Text(String),
+                Move { x: i32, y: i32 },
+                Quit,
+            }
+        }
+
+        #[allow(dead_code)]
+        #[derive(DType)]
+        #[dtype(remote = proto::Message, matcher = match_message)]
+        pub enum MessageDef {
+            Ping(u32),
+            #[dtype(from)]
+            Text(String),
+            Move {
+                x: i32,
+                y: i32,
+            },
+            Quit,
+        }
+
+        #[test]
+        fn test_remote_derive() {
+            let mut ping = proto::Message::Ping(3);
+            if let Some(count) =
+                EnumVariantDowncastMut::<PingVariant>::downcast_mut(&mut ping)
+            {
+                *count += 1;
+            }
+            assert_eq!(
+                EnumVariantDowncastRef::<PingVariant>::downcast_ref(&ping),
+                Some(&4)
+            );
+
+            let messages: [proto::Message; 4] = [
+                ping,
+                String::from("hi").into(),
+                MessageDefMoveFields { x: 1, y: 2 }.into(),
+                QuitVariant.into(),
+            ];
+            let described: Vec<String> = messages
+                .iter()
+                .map(|message| {
+                    match_message!(ref message, {
+                        Ping(count) => format!("ping {}", count),
+                        Quit => "quit".to_string(),
+                        _: MessageDef<Src, Token>(inner) => {
+                            let _: SrcRef<'_> = inner;
+                            std::any::type_name::<Src>().to_string()
+                        },
+                    })
+                })
+                .collect();
+            assert_eq!(
+                described,
+                [
+                    "ping 4",
+                    "alloc::string::String",
+                    std::any::type_name::<MessageDefMoveFields>(),
+                    "quit"
+                ]
+            );
+
+            let built = <proto::Message as EnumVariantConstruct<
+                MoveVariant,
+            >>::construct(MessageDefMoveFields { x: 5, y: 6 });
+            assert_eq!(built, proto::Message::Move { x: 5, y: 6 });
+        }
+    }
 }
@@ -0,0 +1,369 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::{
+    BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, Sub,
+    SubAssign,
+};
+
+use crate::{VariantTokenIndex, VariantWords};
+
+/// A set of variants of `E`, stored as a bitset over `variant_index`.
+///
+/// Sets are built from tokens, either one at a time or from the groups of a
+/// grouped matcher (`match_by_priority!(set GameEvent(Critical))`), and test
+/// values with `contains`. The bits are held in `WORDS` words of 64 bits,
+/// which must be the count the derive implements `VariantWords` for: the
+/// default of one for enums of up to 64 variants, `VariantSet<E, 2>` for up
+/// to 128, and so on.
+pub struct VariantSet<E, const WORDS: usize = 1> {
+    words: [u64; WORDS],
+    marker: PhantomData<fn() -> E>,
+}
+
+impl<E: VariantWords<WORDS>, const WORDS: usize> VariantSet<E, WORDS> {
+    const fn from_words(words: [u64; WORDS]) -> Self {
+        VariantSet {
+            words,
+            marker: PhantomData,
+        }
+    }
+
+    /// Creates an empty set.
+    pub const fn new() -> Self {
+        Self::from_words([0; WORDS])
+    }
+
+    /// Creates the set of every variant.
+    pub const fn all() -> Self {
+        let mut words = [0; WORDS];
+        let mut word = 0;
+        while word < WORDS {
+            let first = word * 64;
+            words[word] = if E::VARIANT_COUNT >= first + 64 {
+                u64::MAX
+            } else if E::VARIANT_COUNT > first {
+                (1 << (E::VARIANT_COUNT - first)) - 1
+            } else {
+                0
+            };
+            word += 1;
+        }
+        Self::from_words(words)
+    }
+
+    /// Creates the set of the variant of `Token`.
+    pub const fn of<Token>() -> Self
+    where
+        E: VariantTokenIndex<Token>,
+    {
+        Self::new().with::<Token>()
+    }
+
+    /// Returns the set with the variant of `Token` added.
+    pub const fn with<Token>(self) -> Self
+    where
+        E: VariantTokenIndex<Token>,
+    {
+        let index = <E as VariantTokenIndex<Token>>::INDEX;
+        let mut words = self.words;
+        words[index / 64] |= 1 << (index % 64);
+        Self::from_words(words)
+    }
+
+    /// Returns the set with the variant of `Token` removed.
+    pub const fn without<Token>(self) -> Self
+    where
+        E: VariantTokenIndex<Token>,
+    {
+        let index = <E as VariantTokenIndex<Token>>::INDEX;
+        let mut words = self.words;
+        words[index / 64] &= !(1 << (index % 64));
+        Self::from_words(words)
+    }
+
+    /// Adds the variant of `Token`, returning whether it was absent.
+    pub fn insert<Token>(&mut self) -> bool
+    where
+        E: VariantTokenIndex<Token>,
+    {
+        self.insert_index(<E as VariantTokenIndex<Token>>::INDEX)
+    }
+
+    /// Removes the variant of `Token`, returning whether it was present.
+    pub fn remove<Token>(&mut self) -> bool
+    where
+        E: VariantTokenIndex<Token>,
+    {
+        self.remove_index(<E as VariantTokenIndex<Token>>::INDEX)
+    }
+
+    /// Adds the variant at `index`, returning whether it was absent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not below `E::VARIANT_COUNT`.
+    pub fn insert_index(&mut self, index: usize) -> bool {
+        assert!(index < E::VARIANT_COUNT, "variant index out of range");
+        let absent = !self.contains_index(index);
+        self.words[index / 64] |= 1 << (index % 64);
+        absent
+    }
+
+    /// Removes the variant at `index`, returning whether it was present.
+    pub fn remove_index(&mut self, index: usize) -> bool {
+        let present = self.contains_index(index);
+        if present {
+            self.words[index / 64] &= !(1 << (index % 64));
+        }
+        present
+    }
+
+    /// Returns `true` if `value` holds one of the variants of the set.
+    pub fn contains(&self, value: &E) -> bool {
+        self.contains_index(value.variant_index())
+    }
+
+    /// Returns `true` if the variant of `Token` is in the set.
+    pub const fn contains_variant<Token>(&self) -> bool
+    where
+        E: VariantTokenIndex<Token>,
+    {
+        self.contains_index(<E as VariantTokenIndex<Token>>::INDEX)
+    }
+
+    /// Returns `true` if the variant at `index` is in the set, and `false`
+    /// for indices past the variants of `E`.
+    pub const fn contains_index(&self, index: usize) -> bool {
+        index < E::VARIANT_COUNT
+            && self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// Returns the number of variants in the set.
+    pub const fn len(&self) -> usize {
+        let mut len = 0;
+        let mut word = 0;
+        while word < WORDS {
+            len += self.words[word].count_ones() as usize;
+            word += 1;
+        }
+        len
+    }
+
+    /// Returns `true` if the set has no variant.
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the variants in either set.
+    pub const fn union(self, other: Self) -> Self {
+        let mut words = self.words;
+        let mut word = 0;
+        while word < WORDS {
+            words[word] |= other.words[word];
+            word += 1;
+        }
+        Self::from_words(words)
+    }
+
+    /// Returns the variants in both sets.
+    pub const fn intersection(self, other: Self) -> Self {
+        let mut words = self.words;
+        let mut word = 0;
+        while word < WORDS {
+            words[word] &= other.words[word];
+            word += 1;
+        }
+        Self::from_words(words)
+    }
+
+    /// Returns the variants in `self` but not in `other`.
+    pub const fn difference(self, other: Self) -> Self {
+        let mut words = self.words;
+        let mut word = 0;
+        while word < WORDS {
+            words[word] &= !other.words[word];
+            word += 1;
+        }
+        Self::from_words(words)
+    }
+
+    /// Returns the variants in exactly one of the sets.
+    pub const fn symmetric_difference(self, other: Self) -> Self {
+        let mut words = self.words;
+        let mut word = 0;
+        while word < WORDS {
+            words[word] ^= other.words[word];
+            word += 1;
+        }
+        Self::from_words(words)
+    }
+
+    /// Returns the variants not in the set.
+    pub const fn complement(self) -> Self {
+        Self::all().difference(self)
+    }
+
+    /// Returns `true` if every variant of `self` is in `other`.
+    pub const fn is_subset(&self, other: &Self) -> bool {
+        self.difference(*other).is_empty()
+    }
+
+    /// Returns `true` if every variant of `other` is in `self`.
+    pub const fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Returns `true` if the sets have no variant in common.
+    pub const fn is_disjoint(&self, other: &Self) -> bool {
+        self.intersection(*other).is_empty()
+    }
+
+    /// Iterates over the indices of the variants in the set, in ascending
+    /// order.
+    pub fn iter(&self) -> VariantSetIter<WORDS> {
+        VariantSetIter::new(self.words)
+    }
+}
+
+impl<E, const WORDS: usize> Clone for VariantSet<E, WORDS> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E, const WORDS: usize> Copy for VariantSet<E, WORDS> {}
+
+impl<E, const WORDS: usize> PartialEq for VariantSet<E, WORDS> {
+    fn eq(&self, other: &Self) -> bool {
+        self.words == other.words
+    }
+}
+
+impl<E, const WORDS: usize> Eq for VariantSet<E, WORDS> {}
+
+impl<E, const WORDS: usize> Hash for VariantSet<E, WORDS> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.words.hash(state);
+    }
+}
+
+impl<E: VariantWords<WORDS>, const WORDS: usize> Default
+    for VariantSet<E, WORDS>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E, const WORDS: usize> fmt::Debug for VariantSet<E, WORDS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set()
+            .entries(VariantSetIter::new(self.words))
+            .finish()
+    }
+}
+
+impl<E: VariantWords<WORDS>, const WORDS: usize> IntoIterator
+    for VariantSet<E, WORDS>
+{
+    type Item = usize;
+    type IntoIter = VariantSetIter<WORDS>;
+
+    fn into_iter(self) -> VariantSetIter<WORDS> {
+        self.iter()
+    }
+}
+
+impl<E: VariantWords<WORDS>, const WORDS: usize> FromIterator<usize>
+    for VariantSet<E, WORDS>
+{
+    fn from_iter<I: IntoIterator<Item = usize>>(indices: I) -> Self {
+        let mut set = Self::new();
+        for index in indices {
+            set.insert_index(index);
+        }
+        set
+    }
+}
+
+macro_rules! set_operator {
+    ($op:ident, $method:ident, $assign:ident, $assign_method:ident, $set_method:ident) => {
+        impl<E: VariantWords<WORDS>, const WORDS: usize> $op
+            for VariantSet<E, WORDS>
+        {
+            type Output = Self;
+
+            fn $method(self, other: Self) -> Self {
+                self.$set_method(other)
+            }
+        }
+
+        impl<E: VariantWords<WORDS>, const WORDS: usize> $assign
+            for VariantSet<E, WORDS>
+        {
+            fn $assign_method(&mut self, other: Self) {
+                *self = self.$set_method(other);
+            }
+        }
+    };
+}
+
+set_operator!(BitOr, bitor, BitOrAssign, bitor_assign, union);
+set_operator!(BitAnd, bitand, BitAndAssign, bitand_assign, intersection);
+set_operator!(
+    BitXor,
+    bitxor,
+    BitXorAssign,
+    bitxor_assign,
+    symmetric_difference
+);
+set_operator!(Sub, sub, SubAssign, sub_assign, difference);
+
+impl<E: VariantWords<WORDS>, const WORDS: usize> Not for VariantSet<E, WORDS> {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        self.complement()
+    }
+}
+
+/// Iterator over the variant indices of a [`VariantSet`], in ascending order.
+#[derive(Clone, Debug)]
+pub struct VariantSetIter<const WORDS: usize = 1> {
+    words: [u64; WORDS],
+    word: usize,
+}
+
+impl<const WORDS: usize> VariantSetIter<WORDS> {
+    fn new(words: [u64; WORDS]) -> Self {
+        VariantSetIter { words, word: 0 }
+    }
+}
+
+impl<const WORDS: usize> Iterator for VariantSetIter<WORDS> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.word < WORDS {
+            let bits = &mut self.words[self.word];
+            if *bits != 0 {
+                let index = bits.trailing_zeros() as usize;
+                *bits &= *bits - 1;
+                return Some(self.word * 64 + index);
+            }
+            self.word += 1;
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.words[self.word.min(WORDS)..]
+            .iter()
+            .map(|bits| bits.count_ones() as usize)
+            .sum();
+        (len, Some(len))
+    }
+}
+
+impl<const WORDS: usize> ExactSizeIterator for VariantSetIter<WORDS> {}
@@ -0,0 +1,396 @@
+//! Forwarding impls of user traits for `#[dtype(delegate(...))]`.
+//!
+//! A derive cannot see the definition of a trait, so traits opt in with
+//! `#[dtype_delegate]`, which defines a macro under the trait's name carrying
+//! its method signatures. The derive invokes that macro with the impl header
+//! and the variants, and the macro hands both halves to `dtype_delegate_impl!`.
+//! The formatting traits of `core::fmt` are known without the attribute.
+
+use indexmap::IndexSet;
+use proc_macro::TokenStream;
+use proc_macro2::{Group, TokenStream as TokenStream2, TokenTree};
+use quote::{ToTokens, format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{
+    Attribute, Error, FnArg, Ident, ItemImpl, ItemTrait, Pat, PatIdent, Path,
+    PathArguments, Token, TraitItem, TraitItemFn, Type, Visibility,
+    parse_macro_input, parse_quote,
+};
+
+use crate::derive::collect_names;
+use crate::site_hash;
+
+/// The formatting traits of `core::fmt`, which all have a single `fmt` method.
+const FMT_TRAITS: &[&str] = &[
+    "Binary", "Debug", "Display", "LowerExp", "LowerHex", "Octal", "Pointer",
+    "UpperExp", "UpperHex",
+];
+
+/// A variant of the enum the trait is delegated for.
+pub struct DelegateVariant {
+    /// Attributes for the match arm, e.g. `#[allow(deprecated)]`.
+    pub attrs: Vec<Attribute>,
+    pub ident: Ident,
+    /// The payload the methods are forwarded to.
+    pub ty: Type,
+}
+
+impl Parse for DelegateVariant {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let ident = input.parse()?;
+        let content;
+        syn::parenthesized!(content in input);
+        Ok(Self {
+            attrs,
+            ident,
+            ty: content.parse()?,
+        })
+    }
+}
+
+impl ToTokens for DelegateVariant {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let Self { attrs, ident, ty } = self;
+        tokens.extend(quote! { #(#attrs)* #ident(#ty) });
+    }
+}
+
+/// Input of `dtype_delegate_impl!`: the trait definition, then the impl header
+/// with an empty body and the variants, as passed on by the trait's macro.
+struct DelegateInput {
+    trait_def: ItemTrait,
+    item_impl: ItemImpl,
+    variants: Punctuated<DelegateVariant, Token![,]>,
+}
+
+impl Parse for DelegateInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let trait_def = input.parse()?;
+        let item_impl = input.parse()?;
+        let content;
+        syn::braced!(content in input);
+        Ok(Self {
+            trait_def,
+            item_impl,
+            variants: content
+                .parse_terminated(DelegateVariant::parse, Token![,])?,
+        })
+    }
+}
+
+/// Implements `#[dtype_delegate]`: emits the trait unchanged, and a macro of
+/// the same name that forwards its signatures to `dtype_delegate_impl!`.
+///
+/// Like matchers, the macro of a public trait is `#[macro_export]`ed under a
+/// mangled name and re-exported next to the trait; traits and macros live in
+/// different namespaces, so importing the trait imports both.
+pub fn dtype_delegate_attr_impl(
+    attr: TokenStream,
+    item: TokenStream,
+) -> TokenStream {
+    if !attr.is_empty() {
+        let attr = TokenStream2::from(attr);
+        return Error::new_spanned(attr, "`dtype_delegate` takes no arguments")
+            .to_compile_error()
+            .into();
+    }
+    let trait_def = parse_macro_input!(item as ItemTrait);
+
+    // Only the signatures are needed to forward the methods; default bodies
+    // are emptied but kept, as they let items without `self` be skipped
+    let mut signatures = trait_def.clone();
+    signatures.attrs.clear();
+    // The visibility is not needed either, and `pub(crate)` is no path
+    signatures.vis = Visibility::Inherited;
+    for item in &mut signatures.items {
+        if let TraitItem::Fn(method) = item {
+            method.attrs.retain(|attr| attr.path().is_ident("cfg"));
+            if let Some(body) = &mut method.default {
+                body.stmts.clear();
+            }
+        }
+    }
+
+    // Paths in the signatures resolve where the enum is defined; `crate::`
+    // paths are made to point at the trait's crate
+    let signatures = with_dollar_crate(signatures.into_token_stream());
+
+    let name = &trait_def.ident;
+    let internal_name = format_ident!(
+        "__dtype_delegate_{}_{:016x}",
+        name,
+        site_hash(&trait_def, name.span())
+    );
+    let vis = &trait_def.vis;
+    let export = matches!(vis, Visibility::Public(_))
+        .then(|| quote! { #[macro_export] });
+
+    quote! {
+        #trait_def
+
+        #[doc(hidden)]
+        #[allow(unused_macros)]
+        #export
+        macro_rules! #internal_name {
+            ({ $($dtype_variant:tt)* } $($input:tt)*) => {
+                $($dtype_variant)*::dtype_delegate_impl! { #signatures $($input)* }
+            };
+        }
+        #[doc(hidden)]
+        #[allow(unused_imports)]
+        #vis use #internal_name as #name;
+    }
+    .into()
+}
+
+/// Expands `dtype_delegate_impl!`, invoked by the macros of `#[dtype_delegate]`.
+pub fn dtype_delegate_impl(input: TokenStream) -> TokenStream {
+    let DelegateInput {
+        trait_def,
+        item_impl,
+        variants,
+    } = parse_macro_input!(input as DelegateInput);
+    let variants: Vec<_> = variants.into_iter().collect();
+    expand(&trait_def, item_impl, &variants)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Returns the definition of `trait_path` if it is one of the formatting
+/// traits, written bare or as `fmt::`, `core::fmt::` or `std::fmt::` paths.
+pub fn builtin_trait(trait_path: &Path) -> Option<ItemTrait> {
+    let segments: Vec<String> = trait_path
+        .segments
+        .iter()
+        .map(|s| s.ident.to_string())
+        .collect();
+    let (name, module) = segments.split_last()?;
+    let is_fmt_module = match module {
+        [] => true,
+        [fmt] => fmt == "fmt",
+        [krate, fmt] => (krate == "core" || krate == "std") && fmt == "fmt",
+        _ => false,
+    };
+    if !is_fmt_module || !FMT_TRAITS.contains(&name.as_str()) {
+        return None;
+    }
+    let ident = format_ident!("{}", name);
+    Some(parse_quote! {
+        trait #ident {
+            fn fmt(
+                &self,
+                f: &mut ::core::fmt::Formatter<'_>,
+            ) -> ::core::fmt::Result;
+        }
+    })
+}
+
+/// Returns the path of the macro defined by `#[dtype_delegate]` for
+/// `trait_path`, which takes no generic arguments.
+pub fn macro_path(trait_path: &Path) -> Path {
+    let mut path = trait_path.clone();
+    if let Some(last) = path.segments.last_mut() {
+        last.arguments = PathArguments::None;
+    }
+    path
+}
+
+/// Fills `item_impl` with methods forwarding each method of `trait_def` to
+/// the payload of the active variant.
+pub fn expand(
+    trait_def: &ItemTrait,
+    mut item_impl: ItemImpl,
+    variants: &[DelegateVariant],
+) -> syn::Result<TokenStream2> {
+    let Some((_, trait_path, _)) = &item_impl.trait_ else {
+        return Err(Error::new_spanned(
+            &item_impl,
+            "`dtype_delegate_impl!` expects a trait impl",
+        ));
+    };
+    let trait_path = trait_path.clone();
+
+    let mut errors: Option<Error> = None;
+    let mut push_error = |error: Error| match &mut errors {
+        Some(existing) => existing.combine(error),
+        None => errors = Some(error),
+    };
+    for item in &trait_def.items {
+        let (ident, has_default, kind) = match item {
+            TraitItem::Fn(method) if method.sig.receiver().is_some() => {
+                match forward_method(
+                    &trait_def.ident,
+                    method,
+                    &trait_path,
+                    variants,
+                ) {
+                    Ok(method) => item_impl.items.push(method),
+                    Err(e) => push_error(e),
+                }
+                continue;
+            }
+            TraitItem::Fn(method) => (
+                &method.sig.ident,
+                method.default.is_some(),
+                "functions without `self`",
+            ),
+            TraitItem::Const(item) => {
+                (&item.ident, item.default.is_some(), "associated constants")
+            }
+            TraitItem::Type(item) => {
+                (&item.ident, item.default.is_some(), "associated types")
+            }
+            _ => continue,
+        };
+        // Items with a default are left to it
+        if !has_default {
+            push_error(Error::new_spanned(
+                ident,
+                format!(
+                    "cannot delegate `{}::{}`: {} need a default, as there \
+                     is no payload to forward to",
+                    trait_def.ident, ident, kind
+                ),
+            ));
+        }
+    }
+    match errors {
+        Some(errors) => Err(errors),
+        None => Ok(item_impl.into_token_stream()),
+    }
+}
+
+/// Generates the impl of `method`, matching on `self` and calling the
+/// method on the payload of each variant.
+fn forward_method(
+    trait_ident: &Ident,
+    method: &TraitItemFn,
+    trait_path: &Path,
+    variants: &[DelegateVariant],
+) -> syn::Result<syn::ImplItem> {
+    let mut sig = method.sig.clone();
+    let method_ident = &sig.ident;
+
+    // Only `self`, `&self` and `&mut self` receivers reach the payload
+    let mut args = Vec::new();
+    // `self` is hygienic: match on the token of the signature, which may
+    // come from the expansion of the trait's macro
+    let mut self_token = <Token![self]>::default();
+    for (i, input) in sig.inputs.iter_mut().enumerate() {
+        match input {
+            FnArg::Receiver(receiver) => {
+                if receiver.colon_token.is_some() {
+                    return Err(Error::new_spanned(
+                        &*receiver,
+                        format!(
+                            "cannot delegate `{}::{}`: only `self`, `&self` and \
+                             `&mut self` receivers are supported",
+                            trait_ident, method_ident
+                        ),
+                    ));
+                }
+                // `mut self` only makes the binding mutable
+                if receiver.reference.is_none() {
+                    receiver.mutability = None;
+                }
+                self_token = receiver.self_token;
+            }
+            FnArg::Typed(arg) => {
+                let ty = &arg.ty;
+                if mentions_self(quote!(#ty)) {
+                    return Err(Error::new_spanned(
+                        ty,
+                        format!(
+                            "cannot delegate `{}::{}`: arguments mentioning \
+                             `Self` cannot be forwarded to a payload",
+                            trait_ident, method_ident
+                        ),
+                    ));
+                }
+                let arg_ident = format_ident!("__arg{}", i);
+                arg.pat = Box::new(Pat::Ident(PatIdent {
+                    attrs: Vec::new(),
+                    by_ref: None,
+                    mutability: None,
+                    ident: arg_ident.clone(),
+                    subpat: None,
+                }));
+                args.push(arg_ident);
+            }
+        }
+    }
+    if let syn::ReturnType::Type(_, ty) = &sig.output {
+        if mentions_self(quote!(#ty)) {
+            return Err(Error::new_spanned(
+                ty,
+                format!(
+                    "cannot delegate `{}::{}`: a return type mentioning `Self` \
+                     cannot be produced by a payload",
+                    trait_ident, method_ident
+                ),
+            ));
+        }
+    }
+
+    let arms = variants.iter().map(|variant| {
+        let DelegateVariant { attrs, ident, ty } = variant;
+        let mut call = quote! {
+            <#ty as #trait_path>::#method_ident(__inner, #(#args),*)
+        };
+        if sig.asyncness.is_some() {
+            call = quote! { #call.await };
+        }
+        if sig.unsafety.is_some() {
+            call = quote! { unsafe { #call } };
+        }
+        quote! { #(#attrs)* Self::#ident(__inner) => #call, }
+    });
+    let cfg_attrs = &method.attrs;
+
+    Ok(parse_quote! {
+        #(#cfg_attrs)*
+        #[inline]
+        #sig {
+            match #self_token {
+                #(#arms)*
+            }
+        }
+    })
+}
+
+/// Replaces `crate` in `tokens` with `$crate`, for use in `macro_rules!`.
+fn with_dollar_crate(tokens: TokenStream2) -> TokenStream2 {
+    let mut tokens = tokens.into_iter().peekable();
+    let mut rewritten = TokenStream2::new();
+    while let Some(tt) = tokens.next() {
+        rewritten.extend(match tt {
+            TokenTree::Group(group) => {
+                let stream = with_dollar_crate(group.stream());
+                let mut new_group = Group::new(group.delimiter(), stream);
+                new_group.set_span(group.span());
+                TokenTree::Group(new_group).into_token_stream()
+            }
+            // Only `crate` starting a path, as in `crate::Type`
+            TokenTree::Ident(ident)
+                if ident == "crate"
+                    && matches!(
+                        tokens.peek(),
+                        Some(TokenTree::Punct(punct)) if punct.as_char() == ':'
+                    ) =>
+            {
+                quote! { $#ident }
+            }
+            tt => tt.into_token_stream(),
+        });
+    }
+    rewritten
+}
+
+/// Returns whether `tokens` mention `Self`.
+fn mentions_self(tokens: TokenStream2) -> bool {
+    let mut idents = IndexSet::new();
+    collect_names(tokens, &mut idents, &mut IndexSet::new());
+    idents.contains("Self")
+}
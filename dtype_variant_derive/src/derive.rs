@@ -1,20 +1,28 @@
 #![allow(non_snake_case)]
 
-use darling::{FromAttributes as _, FromDeriveInput};
+use darling::util::{Flag, PathList};
+use darling::{FromAttributes as _, FromDeriveInput, FromMeta, FromVariant};
 use indexmap::{IndexMap, IndexSet};
 use proc_macro::TokenStream;
-use proc_macro2::TokenStream as TokenStream2;
-use quote::{format_ident, quote};
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
+use quote::{ToTokens, format_ident, quote};
+use syn::ext::IdentExt as _;
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
-use syn::{Attribute, Data, Error, Expr, Field, Generics, Ident, Path};
 use syn::{
-    DataEnum, DeriveInput, Fields, GenericArgument, PathArguments, Type,
-    TypePath, WhereClause, parse_macro_input, parse_quote,
+    Attribute, Data, Error, Expr, Field, GenericParam, Generics, Ident, Path,
+};
+use syn::{
+    DataEnum, DeriveInput, ExprLit, Fields, GenericArgument, Lit,
+    PathArguments, Token, Type, TypeParamBound, TypePath, Visibility,
+    WhereClause, WherePredicate, parse_macro_input, parse_quote,
 };
 
-use crate::dtype_variant_path;
+use crate::delegate::{DelegateVariant, builtin_trait, expand, macro_path};
+use crate::dtype_match::generate_matcher_metadata;
 use crate::grouped_matcher::{DTypeGroupedMatcherArgs, ParsedGroupedMatcher};
-use crate::matcher_gen::{MacroRuleArm, generate_macro_rule_arm};
+use crate::{dtype_variant_path, site_hash};
 
 //============================================================================
 // Error Handling Utilities
@@ -71,19 +79,215 @@ struct DTypeMacroArgs {
     #[darling(default)]
     container: Option<Ident>,
 
-    /// Optional. Path to a trait that inner types of variants must implement.
-    /// Example: `"my_crate::constraints::MyConstraint"`
+    /// Optional. Bounds that inner types of variants must satisfy.
+    /// Bounds that are not a plain path or sum of paths go in a string.
+    /// Example: `MyConstraint`, `"num::Num + Copy + 'static"`, `"From<i64>"`
+    #[darling(default)]
+    constraint: Option<ConstraintBounds>,
+
+    /// Optional. Traits implemented for the enum by forwarding their methods
+    /// to the payload of the active variant. Traits other than the formatting
+    /// traits of `core::fmt` need `#[dtype_delegate]` on their definition.
+    /// Example: `delegate(Display, Processable)`
     #[darling(default)]
-    constraint: Option<Expr>,
+    delegate: PathList,
 
     /// Optional. Identifier for the name of the generated closure-based matcher method.
     /// Example: `"match_variant"`
     #[darling(default)]
     matcher: Option<Ident>,
 
+    /// Optional. Visibility of the matcher macros. Defaults to `pub`, which
+    /// exports them from the crate; any other visibility keeps them crate-local.
+    /// Example: `matcher_vis = "pub(crate)"`
+    #[darling(default)]
+    matcher_vis: Option<Visibility>,
+
+    /// Optional. Path to the `dtype_variant` crate, for when it is re-exported
+    /// through another crate rather than being a direct dependency.
+    /// Example: `crate = my_facade::dtype_variant`
+    #[darling(default, rename = "crate")]
+    crate_path: Option<Path>,
+
+    /// Optional. Path to an enum defined in another crate. The derived enum
+    /// then mirrors its definition, and the impls and matchers are generated
+    /// for the remote enum instead.
+    /// Example: `remote = other_crate::Message`
+    #[darling(default)]
+    remote: Option<Path>,
+
+    /// Optional. For enums whose variants are all unit variants: the enum
+    /// carrying the payloads of their tokens, which gives them `of::<T>()`.
+    /// Example: `payloads = DynChunk`
+    #[darling(default)]
+    payloads: Option<Path>,
+
     /// Optional. If true, skips generating From impls for the enum variants.
     #[darling(default)]
     skip_from_impls: bool,
+
+    /// Optional. If true, generates named accessors for each variant, such as
+    /// `as_player_move`, `as_player_move_mut`, `into_player_move` and
+    /// `is_player_move` for a `PlayerMove` variant.
+    #[darling(default)]
+    accessors: bool,
+
+    /// Optional. Generates a struct-of-arrays companion with this name, which
+    /// stores the payloads of each variant in a `Vec` of their own.
+    /// Example: `buckets = EventBuckets`
+    #[darling(default)]
+    buckets: Option<Ident>,
+
+    /// The visibility of the enum, inherited by the generated structs.
+    vis: Visibility,
+
+    /// Optional. Derives for the `{Enum}{Variant}Fields` structs of struct variants.
+    /// Defaults to `Debug, Clone, PartialEq`.
+    /// Example: `fields_derive(Debug, Hash, Eq, serde::Serialize)`
+    #[darling(default)]
+    fields_derive: Option<PathList>,
+
+    /// Optional. Derives for the `{Enum}{Variant}Ref` structs of struct variants.
+    /// Example: `ref_derive(Debug, Copy, Clone)`
+    #[darling(default)]
+    ref_derive: PathList,
+
+    /// Optional. Derives for the `{Enum}{Variant}Mut` structs of struct variants.
+    /// Example: `mut_derive(Debug)`
+    #[darling(default)]
+    mut_derive: PathList,
+
+    /// Optional. Further attributes copied from variants and fields onto the
+    /// `{Enum}{Variant}Fields` structs, next to `cfg`, `doc` and `deprecated`.
+    /// Example: `forward_attrs(serde)`
+    #[darling(default)]
+    forward_attrs: PathList,
+}
+
+/// Parses the `#[dtype(...)]` attribute applied to an enum variant.
+#[derive(Debug, Default, FromVariant)]
+#[darling(attributes(dtype))]
+struct DTypeVariantArgs {
+    /// Optional. Overrides the enum's `constraint` for this variant.
+    #[darling(default)]
+    constraint: Option<ConstraintBounds>,
+
+    /// Optional. Generates `From<payload>` for this variant even with `skip_from_impls`.
+    #[darling(default)]
+    from: Flag,
+
+    /// Optional. Skips `From<payload>` for this variant.
+    #[darling(default)]
+    no_from: Flag,
+}
+
+/// A `+`-separated list of trait and lifetime bounds, such as
+/// `Num + Copy + 'static`.
+#[derive(Debug, Clone)]
+pub struct ConstraintBounds(Punctuated<TypeParamBound, Token![+]>);
+
+impl FromMeta for ConstraintBounds {
+    fn from_expr(expr: &Expr) -> darling::Result<Self> {
+        let bounds = match expr {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(lit), ..
+            }) => lit.parse_with(Punctuated::parse_separated_nonempty),
+            // `Trait` and `TraitA + TraitB` are valid expressions
+            _ => Punctuated::parse_separated_nonempty
+                .parse2(expr.to_token_stream()),
+        };
+        bounds.map(ConstraintBounds).map_err(|e| {
+            darling::Error::custom(format!(
+                "expected trait or lifetime bounds: {}",
+                e
+            ))
+            .with_span(expr)
+        })
+    }
+}
+
+impl ToTokens for ConstraintBounds {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        self.0.to_tokens(tokens);
+    }
+}
+
+/// Visibility, derives and forwarded attributes applied to the structs
+/// generated for struct variants.
+struct GeneratedStructConfig {
+    vis: Visibility,
+    fields_derive: Vec<Path>,
+    ref_derive: Vec<Path>,
+    mut_derive: Vec<Path>,
+    forward_attrs: Vec<Path>,
+}
+
+impl GeneratedStructConfig {
+    fn from_args(args: &DTypeMacroArgs) -> Self {
+        let fields_derive = match &args.fields_derive {
+            Some(paths) => paths.to_vec(),
+            None => vec![
+                parse_quote!(Debug),
+                parse_quote!(Clone),
+                parse_quote!(PartialEq),
+            ],
+        };
+        GeneratedStructConfig {
+            vis: args.vis.clone(),
+            fields_derive,
+            ref_derive: args.ref_derive.to_vec(),
+            mut_derive: args.mut_derive.to_vec(),
+            forward_attrs: args.forward_attrs.to_vec(),
+        }
+    }
+}
+
+/// Attributes of a variant or field copied onto the items generated for it.
+#[derive(Debug, Clone, Default)]
+pub struct ForwardedAttrs {
+    /// `#[cfg(..)]`, copied onto every generated item.
+    pub cfg: Vec<Attribute>,
+    /// `#[doc]` and `#[deprecated]`, copied onto the generated types.
+    pub docs: Vec<Attribute>,
+    /// Attributes allow-listed by `forward_attrs`, copied onto `Fields` structs.
+    pub extra: Vec<Attribute>,
+    /// Whether the variant, or one of its fields, is deprecated.
+    pub deprecated: bool,
+}
+
+impl ForwardedAttrs {
+    fn new(attrs: &[Attribute], forward_attrs: &[Path]) -> Self {
+        let mut forwarded = ForwardedAttrs::default();
+        for attr in attrs {
+            let path = attr.path();
+            if path.is_ident("cfg") {
+                forwarded.cfg.push(attr.clone());
+            } else if path.is_ident("doc") || path.is_ident("deprecated") {
+                forwarded.deprecated |= path.is_ident("deprecated");
+                forwarded.docs.push(attr.clone());
+            } else if forward_attrs.contains(path) {
+                forwarded.extra.push(attr.clone());
+            }
+        }
+        forwarded
+    }
+
+    /// Attributes for the impls and match arms generated for a variant: its
+    /// `cfg`, and an `allow` for uses of deprecated variants and fields.
+    fn item_attrs(&self) -> TokenStream2 {
+        let cfg = &self.cfg;
+        let allow = self.deprecated.then(|| quote! { #[allow(deprecated)] });
+        quote! { #(#cfg)* #allow }
+    }
+}
+
+/// Generates `#[derive(...)]`, or nothing for an empty derive list.
+fn derive_attribute(derives: &[Path]) -> TokenStream2 {
+    if derives.is_empty() {
+        quote! {}
+    } else {
+        quote! { #[derive(#(#derives),*)] }
+    }
 }
 
 /// Comprehensive information about a parsed enum variant.
@@ -121,6 +325,28 @@ pub struct ParsedVariantInfo {
     pub mut_type: Option<Type>,
     /// Field information for struct variants. `None` for non-struct variants.
     pub struct_fields: Option<Vec<Field>>,
+    /// The subset of the enum's generics used by the fields of a struct
+    /// variant, declared by its `Fields` struct. `None` for non-struct variants.
+    pub struct_generics: Option<Generics>,
+    /// The variant's own `constraint`, overriding the enum's.
+    pub constraint: Option<ConstraintBounds>,
+    /// Per-variant `from`/`no_from` choice, overriding `skip_from_impls`.
+    pub from_override: Option<bool>,
+    /// Attributes copied from the variant onto the items generated for it.
+    pub attrs: ForwardedAttrs,
+}
+
+impl ParsedVariantInfo {
+    /// Applies the variant's `cfg`, and an `allow` for deprecated uses, to the
+    /// impls generated for it by scoping them in an anonymous const.
+    fn scoped_items(&self, items: TokenStream2) -> TokenStream2 {
+        let attrs = self.attrs.item_attrs();
+        if attrs.is_empty() {
+            items
+        } else {
+            quote! { #attrs const _: () = { #items }; }
+        }
+    }
 }
 
 //============================================================================
@@ -149,8 +375,6 @@ pub struct ParsedVariantInfo {
 /// - Malformed grouped matcher definitions
 /// - Missing shared variant tokens
 pub fn dtype_derive_impl(input: TokenStream) -> TokenStream {
-    let dtype_variant_path = dtype_variant_path();
-
     // Parse the input token stream into a syn::DeriveInput AST node.
     let derive_input = parse_macro_input!(input as DeriveInput);
 
@@ -160,6 +384,15 @@ pub fn dtype_derive_impl(input: TokenStream) -> TokenStream {
         Err(e) => return e.write_errors().into(),
     };
 
+    // Use the `crate` override, or find `dtype_variant` in the manifest.
+    let dtype_variant_path = match &main_args.crate_path {
+        Some(path) => path.clone(),
+        None => match dtype_variant_path(main_args.ident.span()) {
+            Ok(path) => path,
+            Err(e) => return e.to_compile_error().into(),
+        },
+    };
+
     // Perform initial validation: ensure it's an enum.
     let enum_data = match &derive_input.data {
         Data::Enum(data) => data,
@@ -200,12 +433,26 @@ pub fn dtype_derive_impl(input: TokenStream) -> TokenStream {
     };
 
     // Parse enum variants and extract necessary information
-    let parsed_variants =
-        match parse_variants(enum_data, &container_ident_opt, &main_args.ident)
-        {
-            Ok(variants) => variants,
-            Err(e) => return e.to_compile_error().into(),
-        };
+    let struct_config = GeneratedStructConfig::from_args(&main_args);
+    let mut parsed_variants = match parse_variants(
+        enum_data,
+        &container_ident_opt,
+        &main_args.ident,
+        &main_args.generics,
+        &struct_config,
+    ) {
+        Ok(variants) => variants,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    // The payloads of a remote enum are usually foreign as well, and the orphan
+    // rule forbids `From` impls between two foreign types. Tuple variants opt
+    // in with `#[dtype(from)]`; the generated `Fields` structs are local.
+    if main_args.remote.is_some() {
+        for v in parsed_variants.iter_mut().filter(|v| !v.is_struct) {
+            v.from_override.get_or_insert(false);
+        }
+    }
 
     // Parse #[dtype_grouped_matcher] attributes using darling
     let mut parsed_grouped_matchers = Vec::new();
@@ -253,6 +500,26 @@ pub fn dtype_derive_impl(input: TokenStream) -> TokenStream {
             validation_errors = Some(combine_errors(validation_errors, e));
         }
     }
+    if let Err(e) = validate_from_impls(
+        &parsed_variants,
+        &main_args.ident,
+        main_args.skip_from_impls,
+    ) {
+        validation_errors = Some(combine_errors(validation_errors, e));
+    }
+    if let Some(payloads) = &main_args.payloads {
+        if !parsed_variants.iter().all(|v| v.is_unit) {
+            let e = Error::new_spanned(
+                payloads,
+                create_error_message(
+                    "DType derive macro validation",
+                    "`payloads` is for enums whose variants are all unit \
+                     variants, as they carry no payloads of their own",
+                ),
+            );
+            validation_errors = Some(combine_errors(validation_errors, e));
+        }
+    }
     if let Some(errors) = validation_errors {
         return errors.to_compile_error().into();
     }
@@ -260,6 +527,13 @@ pub fn dtype_derive_impl(input: TokenStream) -> TokenStream {
     // Extract components for code generation.
     let enum_name = &main_args.ident;
     let generics = &main_args.generics;
+    let matcher_scope = MatcherScope::new(&derive_input, &main_args);
+    // Trait impls are for the remote enum when mirroring one
+    let enum_type: Path = match &main_args.remote {
+        Some(remote) => remote.clone(),
+        None => enum_name.clone().into(),
+    };
+    let is_remote = main_args.remote.is_some();
 
     // Generate the different code blocks using helper functions.
     let struct_definitions = generate_struct_definitions(&parsed_variants);
@@ -278,31 +552,151 @@ pub fn dtype_derive_impl(input: TokenStream) -> TokenStream {
     let target_impls = generate_enum_variant_downcast(
         &dtype_variant_path,
         enum_name,
+        &enum_type,
+        generics,
+        &parsed_variants,
+        &tokens_path,
+    );
+    let construct_impls = generate_enum_variant_construct(
+        &dtype_variant_path,
+        &enum_type,
         generics,
         &parsed_variants,
         &tokens_path,
     );
     let constraint_impls = generate_enum_variant_constraint(
         &dtype_variant_path,
-        enum_name,
+        &enum_type,
         generics,
         &parsed_variants,
         &constraint_path_opt,
         &tokens_path, // Pass tokens_path
     );
-    let from_impls = generate_from_impls(
+    let container_marker = generate_container_marker(
+        &dtype_variant_path,
         enum_name,
+        &container_ident_opt,
+        &struct_config.vis,
+    );
+    let payload_impls = generate_variant_payload_impls(
+        &dtype_variant_path,
+        &enum_type,
+        generics,
+        &parsed_variants,
+        &container_marker,
+        &tokens_path,
+    );
+    let payload_token_impls = generate_payload_token_impls(
+        &dtype_variant_path,
+        &enum_type,
+        generics,
+        &parsed_variants,
+        &tokens_path,
+        is_remote,
+    );
+    let from_impls = generate_from_impls(
+        &enum_type,
         generics,
         &parsed_variants,
         container_ident_opt.is_some(),
         &tokens_path,
         main_args.skip_from_impls,
     );
-    let downcast_methods = generate_downcast_methods(
+    let delegate_impls = match generate_delegate_impls(
+        &dtype_variant_path,
+        &enum_type,
+        generics,
+        &parsed_variants,
+        &main_args.delegate,
+    ) {
+        Ok(impls) => impls,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    // Inherent methods cannot be added to a remote enum; its downcasts are
+    // reached through the `EnumVariantDowncast*` traits.
+    let (inherent_methods, remote_check) = match &main_args.remote {
+        Some(remote) if main_args.accessors => {
+            return Error::new_spanned(
+                remote,
+                create_error_message(
+                    "DType derive macro validation",
+                    "`accessors` are inherent methods, which cannot be added \
+                     to a remote enum",
+                ),
+            )
+            .to_compile_error()
+            .into();
+        }
+        Some(remote) => (
+            TokenStream2::new(),
+            generate_remote_check(remote, generics, &parsed_variants),
+        ),
+        None => {
+            let from_variant_methods = generate_from_variant_methods(
+                &dtype_variant_path,
+                enum_name,
+                generics,
+                &parsed_variants,
+                main_args.payloads.as_ref(),
+            );
+            let downcast_methods = generate_downcast_methods(
+                &dtype_variant_path,
+                enum_name,
+                generics,
+                &parsed_variants,
+            );
+            let variant_index_methods = generate_variant_index_methods(
+                &dtype_variant_path,
+                enum_name,
+                generics,
+                &parsed_variants,
+                &tokens_path,
+            );
+            let accessor_methods = if main_args.accessors {
+                generate_accessor_methods(
+                    &dtype_variant_path,
+                    enum_name,
+                    generics,
+                    &parsed_variants,
+                    &tokens_path,
+                )
+            } else {
+                TokenStream2::new()
+            };
+            let inherent_methods = quote! {
+                // Implementation block containing from_variant and of.
+                #from_variant_methods
+
+                // Implementation block containing downcast methods.
+                #downcast_methods
+
+                // Implementation block containing the variant index methods.
+                #variant_index_methods
+
+                // Implementation block containing the named accessors.
+                #accessor_methods
+            };
+            (inherent_methods, TokenStream2::new())
+        }
+    };
+    let buckets = main_args.buckets.as_ref().map(|buckets_ident| {
+        generate_buckets(
+            &dtype_variant_path,
+            enum_name,
+            &enum_type,
+            buckets_ident,
+            &struct_config.vis,
+            generics,
+            &parsed_variants,
+            &tokens_path,
+        )
+    });
+    let token_at_impls = generate_variant_token_at_impls(
         &dtype_variant_path,
         enum_name,
         generics,
         &parsed_variants,
+        &tokens_path,
     );
     let matcher_method = generate_matcher_method(
         &dtype_variant_path,
@@ -311,6 +705,7 @@ pub fn dtype_derive_impl(input: TokenStream) -> TokenStream {
         &parsed_variants,
         &matcher_ident_opt,
         &tokens_path,
+        &matcher_scope,
     );
     let grouped_matcher_macro = parsed_grouped_matchers
         .iter() // Iterate over references
@@ -323,6 +718,7 @@ pub fn dtype_derive_impl(input: TokenStream) -> TokenStream {
                 &parsed_variants,
                 parsed_grouped_matcher, // Pass the reference
                 &tokens_path,
+                &matcher_scope,
             )
         })
         .fold(TokenStream2::new(), |mut acc, s| {
@@ -345,17 +741,39 @@ pub fn dtype_derive_impl(input: TokenStream) -> TokenStream {
         // Compile-time validation of shared variant ZST token existence.
         #token_validation_code
 
+        // Compile-time validation that a mirrored remote enum has no other variants.
+        #remote_check
+
         // Implementations of #dtype_variant_path::EnumVariantTarget trait.
         #target_impls
 
+        // Implementations of #dtype_variant_path::EnumVariantConstruct trait.
+        #construct_impls
+
         // Implementations of the user-specified constraint trait.
         #constraint_impls
 
+        // Implementations of #dtype_variant_path::VariantPayload trait.
+        #payload_impls
+
+        // Implementations of #dtype_variant_path::PayloadToken trait.
+        #payload_token_impls
+
         // Implementations of From<PayloadType> for the enum.
         #from_impls
 
-        // Implementation block containing downcast methods.
-        #downcast_methods
+        // Forwarding impls of the `delegate` traits.
+        #delegate_impls
+
+        // Inherent methods (none for remote enums).
+        #inherent_methods
+
+        // The `buckets` struct-of-arrays companion, if requested.
+        #buckets
+
+        // Implementations of #dtype_variant_path::VariantTokenAt, naming the
+        // tokens for the matchers.
+        #token_at_impls
 
         // Implementation block containing the matcher method.
         #matcher_method
@@ -377,7 +795,7 @@ pub fn dtype_derive_impl(input: TokenStream) -> TokenStream {
 struct ParsedPaths {
     shared_variant_zst_path_opt: Option<Path>,
     container_ident_opt: Option<Ident>,
-    constraint_path_opt: Option<Expr>,
+    constraint_path_opt: Option<ConstraintBounds>,
     matcher_ident_opt: Option<Ident>,
 }
 
@@ -385,7 +803,7 @@ fn parse_config_paths(args: &DTypeMacroArgs) -> Result<ParsedPaths, Error> {
     let shared_variant_zst_path: Option<Path> =
         args.shared_variant_zst_path.clone();
     let container_ident: Option<Ident> = args.container.clone();
-    let constraint_path: Option<Expr> = args.constraint.clone();
+    let constraint_path: Option<ConstraintBounds> = args.constraint.clone();
     let matcher_ident: Option<Ident> = args.matcher.clone();
 
     Ok(ParsedPaths {
@@ -400,8 +818,11 @@ fn parse_variants(
     enum_data: &DataEnum,
     container_ident: &Option<Ident>,
     enum_name: &Ident,
+    generics: &Generics,
+    struct_config: &GeneratedStructConfig,
 ) -> Result<Vec<ParsedVariantInfo>, Error> {
     let mut variants_info = Vec::new();
+    let vis = &struct_config.vis;
 
     for variant in &enum_data.variants {
         let variant_ident = variant.ident.clone();
@@ -412,8 +833,49 @@ fn parse_variants(
             span = variant_ident.span()
         );
 
+        let variant_args = DTypeVariantArgs::from_variant(variant)
+            .map_err(darling_error_to_syn)?;
+        let mut attrs =
+            ForwardedAttrs::new(&variant.attrs, &struct_config.forward_attrs);
+        let constraint = variant_args.constraint;
+        let from_override = match (
+            variant_args.from.is_present(),
+            variant_args.no_from.is_present(),
+        ) {
+            (true, true) => {
+                return Err(Error::new(
+                    variant_args.no_from.span(),
+                    create_error_message(
+                        "Variant From validation",
+                        "`from` and `no_from` cannot be used together",
+                    ),
+                ));
+            }
+            (true, false) => Some(true),
+            (false, true) => Some(false),
+            (false, false) => None,
+        };
+
         match &variant.fields {
             Fields::Unit => {
+                if let Some(constraint) = &constraint {
+                    return Err(Error::new_spanned(
+                        constraint,
+                        create_error_message(
+                            "Variant constraint validation",
+                            "unit variants have no payload to constrain",
+                        ),
+                    ));
+                }
+                if from_override.is_some() {
+                    return Err(Error::new_spanned(
+                        variant,
+                        create_error_message(
+                            "Variant From validation",
+                            "unit variants are always converted from their token; `from` and `no_from` only apply to payload variants",
+                        ),
+                    ));
+                }
                 variants_info.push(ParsedVariantInfo {
                     variant_ident,
                     token_ident,
@@ -427,6 +889,10 @@ fn parse_variants(
                     ref_type: None,
                     mut_type: None,
                     struct_fields: None,
+                    struct_generics: None,
+                    constraint: None,
+                    from_override: None,
+                    attrs,
                 });
             }
             Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
@@ -448,6 +914,10 @@ fn parse_variants(
                     ref_type: None,
                     mut_type: None,
                     struct_fields: None,
+                    struct_generics: None,
+                    constraint,
+                    from_override,
+                    attrs,
                 });
             }
             Fields::Named(named_fields) => {
@@ -475,56 +945,106 @@ fn parse_variants(
                 let fields_vec: Vec<Field> =
                     named_fields.named.iter().cloned().collect();
 
-                let field_defs = named_fields.named.iter().map(|field| {
-                    let field_name = &field.ident;
-                    let field_type = &field.ty;
-                    quote! {
-                        pub #field_name: #field_type,
-                    }
-                });
+                // The generated structs only declare the enum generics their
+                // fields use; the `Ref`/`Mut` structs borrow for `'target`.
+                let struct_generics =
+                    generics_used_by_fields(generics, &fields_vec);
+                let borrowed_generics = with_target_lifetime(&struct_generics);
+                let (_, struct_ty_generics, struct_where_clause) =
+                    struct_generics.split_for_impl();
+                let (_, borrowed_ty_generics, _) =
+                    borrowed_generics.split_for_impl();
+
+                let field_attrs: Vec<ForwardedAttrs> = fields_vec
+                    .iter()
+                    .map(|field| {
+                        ForwardedAttrs::new(
+                            &field.attrs,
+                            &struct_config.forward_attrs,
+                        )
+                    })
+                    .collect();
+                attrs.deprecated |= field_attrs.iter().any(|a| a.deprecated);
+
+                let field_defs = fields_vec.iter().zip(&field_attrs).map(
+                    |(field, attrs)| {
+                        let field_name = &field.ident;
+                        let field_type = &field.ty;
+                        let ForwardedAttrs {
+                            cfg, docs, extra, ..
+                        } = attrs;
+                        quote! {
+                            #(#cfg)* #(#docs)* #(#extra)*
+                            #vis #field_name: #field_type,
+                        }
+                    },
+                );
 
-                let field_ref_defs = named_fields.named.iter().map(|field| {
-                    let field_name = &field.ident;
-                    let field_type = &field.ty;
-                    quote! {
-                        pub #field_name: &'target #field_type,
-                    }
-                });
+                let field_ref_defs = fields_vec.iter().zip(&field_attrs).map(
+                    |(field, attrs)| {
+                        let field_name = &field.ident;
+                        let field_type = &field.ty;
+                        let ForwardedAttrs { cfg, docs, .. } = attrs;
+                        quote! {
+                            #(#cfg)* #(#docs)*
+                            #vis #field_name: &'target #field_type,
+                        }
+                    },
+                );
 
-                let field_mut_defs = named_fields.named.iter().map(|field| {
-                    let field_name = &field.ident;
-                    let field_type = &field.ty;
-                    quote! {
-                        pub #field_name: &'target mut #field_type,
-                    }
-                });
+                let field_mut_defs = fields_vec.iter().zip(&field_attrs).map(
+                    |(field, attrs)| {
+                        let field_name = &field.ident;
+                        let field_type = &field.ty;
+                        let ForwardedAttrs { cfg, docs, .. } = attrs;
+                        quote! {
+                            #(#cfg)* #(#docs)*
+                            #vis #field_name: &'target mut #field_type,
+                        }
+                    },
+                );
 
                 // Generate the struct definitions
+                let fields_derive =
+                    derive_attribute(&struct_config.fields_derive);
+                let ref_derive = derive_attribute(&struct_config.ref_derive);
+                let mut_derive = derive_attribute(&struct_config.mut_derive);
+                let ForwardedAttrs {
+                    cfg, docs, extra, ..
+                } = &attrs;
+
                 let struct_definition = quote! {
-                    #[derive(Debug, Clone, PartialEq)]
-                    pub struct #struct_ident {
+                    #(#cfg)* #(#docs)*
+                    #fields_derive
+                    #(#extra)*
+                    #vis struct #struct_ident #struct_generics #struct_where_clause {
                         #(#field_defs)*
                     }
                 };
 
                 let struct_ref_definition = quote! {
-                    pub struct #struct_ref_ident<'target> {
+                    #(#cfg)* #(#docs)*
+                    #ref_derive
+                    #vis struct #struct_ref_ident #borrowed_generics #struct_where_clause {
                         #(#field_ref_defs)*
                     }
                 };
 
                 let struct_mut_definition = quote! {
-                    pub struct #struct_mut_ident<'target> {
+                    #(#cfg)* #(#docs)*
+                    #mut_derive
+                    #vis struct #struct_mut_ident #borrowed_generics #struct_where_clause {
                         #(#field_mut_defs)*
                     }
                 };
 
                 // The struct type becomes both full_field_type and inner_type
-                let struct_type: Type = syn::parse_quote!(#struct_ident);
+                let struct_type: Type =
+                    syn::parse_quote!(#struct_ident #struct_ty_generics);
                 let struct_ref_type: Type =
-                    syn::parse_quote!(#struct_ref_ident);
+                    syn::parse_quote!(#struct_ref_ident #borrowed_ty_generics);
                 let struct_mut_type: Type =
-                    syn::parse_quote!(#struct_mut_ident);
+                    syn::parse_quote!(#struct_mut_ident #borrowed_ty_generics);
                 let full_field_type = if let Some(_container) = container_ident
                 {
                     // If container is specified, we would wrap it, but struct variants
@@ -547,6 +1067,10 @@ fn parse_variants(
                     ref_type: Some(struct_ref_type),
                     mut_type: Some(struct_mut_type),
                     struct_fields: Some(fields_vec),
+                    struct_generics: Some(struct_generics),
+                    constraint,
+                    from_override,
+                    attrs,
                 });
             }
             Fields::Unnamed(_) => {
@@ -626,6 +1150,189 @@ fn extract_inner_type(
     }
 }
 
+/// Collects the identifiers and lifetime names appearing in `tokens`.
+pub(crate) fn collect_names(
+    tokens: TokenStream2,
+    idents: &mut IndexSet<String>,
+    lifetimes: &mut IndexSet<String>,
+) {
+    let mut after_apostrophe = false;
+    for tt in tokens {
+        match &tt {
+            TokenTree::Group(group) => {
+                collect_names(group.stream(), idents, lifetimes)
+            }
+            TokenTree::Ident(ident) if after_apostrophe => {
+                lifetimes.insert(ident.to_string());
+            }
+            TokenTree::Ident(ident) => {
+                idents.insert(ident.to_string());
+            }
+            _ => {}
+        }
+        after_apostrophe =
+            matches!(tt, TokenTree::Punct(p) if p.as_char() == '\'');
+    }
+}
+
+/// Returns the name of a generic parameter and whether it is a lifetime.
+fn generic_param_name(param: &GenericParam) -> (String, bool) {
+    match param {
+        GenericParam::Lifetime(def) => (def.lifetime.ident.to_string(), true),
+        GenericParam::Type(ty) => (ty.ident.to_string(), false),
+        GenericParam::Const(c) => (c.ident.to_string(), false),
+    }
+}
+
+/// Returns the subset of `generics` used by `fields`.
+///
+/// A parameter is kept if a field type mentions it, or if it is mentioned by
+/// the bounds of a kept parameter; where-predicates are kept if they mention
+/// a kept parameter. This lets struct variants of generic enums get their own
+/// structs without unused (and thus rejected) parameters.
+fn generics_used_by_fields(generics: &Generics, fields: &[Field]) -> Generics {
+    let mut idents = IndexSet::new();
+    let mut lifetimes = IndexSet::new();
+    for field in fields {
+        let ty = &field.ty;
+        collect_names(quote!(#ty), &mut idents, &mut lifetimes);
+    }
+
+    let is_used = |param: &GenericParam,
+                   idents: &IndexSet<String>,
+                   lifetimes: &IndexSet<String>| {
+        let (name, is_lifetime) = generic_param_name(param);
+        if is_lifetime {
+            lifetimes.contains(&name)
+        } else {
+            idents.contains(&name)
+        }
+    };
+    let mentions_used = |tokens: TokenStream2,
+                         idents: &IndexSet<String>,
+                         lifetimes: &IndexSet<String>| {
+        let mut mentioned_idents = IndexSet::new();
+        let mut mentioned_lifetimes = IndexSet::new();
+        collect_names(tokens, &mut mentioned_idents, &mut mentioned_lifetimes);
+        generics.params.iter().any(|param| {
+            is_used(param, idents, lifetimes)
+                && is_used(param, &mentioned_idents, &mentioned_lifetimes)
+        })
+    };
+
+    // Grow the used set until the bounds of used parameters add nothing new.
+    loop {
+        let before = idents.len() + lifetimes.len();
+        for param in &generics.params {
+            if is_used(param, &idents, &lifetimes) {
+                collect_names(quote!(#param), &mut idents, &mut lifetimes);
+            }
+        }
+        for predicate in
+            generics.where_clause.iter().flat_map(|w| &w.predicates)
+        {
+            if mentions_used(quote!(#predicate), &idents, &lifetimes) {
+                collect_names(quote!(#predicate), &mut idents, &mut lifetimes);
+            }
+        }
+        if idents.len() + lifetimes.len() == before {
+            break;
+        }
+    }
+
+    let params = generics
+        .params
+        .iter()
+        .filter(|param| is_used(param, &idents, &lifetimes))
+        .cloned()
+        .collect();
+    let where_clause =
+        generics
+            .where_clause
+            .as_ref()
+            .map(|where_clause| WhereClause {
+                where_token: where_clause.where_token,
+                predicates: where_clause
+                    .predicates
+                    .iter()
+                    .filter(|predicate| {
+                        mentions_used(quote!(#predicate), &idents, &lifetimes)
+                    })
+                    .cloned()
+                    .collect(),
+            });
+    Generics {
+        params,
+        where_clause,
+        ..generics.clone()
+    }
+}
+
+/// Prepends the `'target` borrow lifetime of the `Ref`/`Mut` structs.
+fn with_target_lifetime(generics: &Generics) -> Generics {
+    let mut generics = generics.clone();
+    generics.params.insert(0, parse_quote!('target));
+    if generics.lt_token.is_none() {
+        generics.lt_token = Some(Default::default());
+        generics.gt_token = Some(Default::default());
+    }
+    generics
+}
+
+/// Returns whether `From<payload>` is generated for a payload variant.
+fn has_from_impl(variant: &ParsedVariantInfo, skip_from_impls: bool) -> bool {
+    variant.from_override.unwrap_or(!skip_from_impls)
+}
+
+/// Validates that no two variants generate `From` for the same payload type,
+/// reporting every variant sharing the type.
+fn validate_from_impls(
+    parsed_variants: &[ParsedVariantInfo],
+    enum_name: &Ident,
+    skip_from_impls: bool,
+) -> Result<(), Error> {
+    // Payload type (by its tokens) -> the variants converting from it
+    let mut sources: IndexMap<String, Vec<&ParsedVariantInfo>> =
+        IndexMap::new();
+    for v in parsed_variants
+        .iter()
+        .filter(|v| !v.is_unit && has_from_impl(v, skip_from_impls))
+    {
+        if let Some(ty) = &v.full_field_type {
+            sources.entry(quote!(#ty).to_string()).or_default().push(v);
+        }
+    }
+
+    let mut errors: Option<Error> = None;
+    for variants in sources.values().filter(|variants| variants.len() > 1) {
+        let names = variants
+            .iter()
+            .map(|v| format!("`{}`", v.variant_ident))
+            .collect::<Vec<_>>()
+            .join(", ");
+        for v in variants {
+            let error = Error::new_spanned(
+                v.full_field_type.as_ref().unwrap(),
+                create_error_message(
+                    "From impl validation",
+                    &format!(
+                        "variants {} carry the same payload type, so their `From` impls \
+                         would conflict; add `#[dtype(no_from)]` to all but one of them and \
+                         build the others with `{}::new::<Token>(..)`",
+                        names, enum_name
+                    ),
+                ),
+            );
+            errors = Some(combine_errors(errors, error));
+        }
+    }
+
+    match errors {
+        Some(errors) => Err(errors),
+        None => Ok(()),
+    }
+}
+
 /// Validates the parsed named grouped matcher against the enum variants.
 fn validate_grouped_matcher(
     parsed_grouped_matcher: &ParsedGroupedMatcher,
@@ -707,34 +1414,22 @@ fn generate_macro_compatible_paths(
     tokens_path: &Path,
     dtype_variant_path: &Path,
 ) -> (TokenStream2, TokenStream2) {
-    let use_token_path_crate_macro = tokens_path
-        .segments
-        .first()
-        .map(|seg| seg.ident == "crate")
-        .unwrap_or(false);
-    let use_dtype_variant_path_crate_macro = dtype_variant_path
-        .segments
-        .first()
-        .map(|seg| seg.ident == "crate")
-        .unwrap_or(false);
-
-    let tokens_path_macro = if use_token_path_crate_macro {
-        let mut rest_path = TokenStream2::new();
-        for segment in tokens_path.segments.iter().skip(1) {
-            rest_path.extend(quote!( :: #segment));
-        }
-        quote! { $crate #rest_path }
-    } else {
-        quote! { #tokens_path }
-    };
-
-    let dtype_variant_path_macro = if use_dtype_variant_path_crate_macro {
-        quote! { $crate }
-    } else {
-        quote! { #dtype_variant_path }
-    };
+    (
+        macro_compatible_path(tokens_path),
+        macro_compatible_path(dtype_variant_path),
+    )
+}
 
-    (tokens_path_macro, dtype_variant_path_macro)
+/// Rewrites a leading `crate` to `$crate`, so the path resolves to the
+/// defining crate wherever the macro is invoked.
+fn macro_compatible_path(path: &Path) -> TokenStream2 {
+    match path.segments.first() {
+        Some(first) if first.ident == "crate" => {
+            let rest = path.segments.iter().skip(1);
+            quote! { $crate #(:: #rest)* }
+        }
+        _ => quote! { #path },
+    }
 }
 
 /// Generates local variant ZST token definitions when shared_variant_zst_path is not specified.
@@ -743,7 +1438,9 @@ fn generate_local_token_definitions(
 ) -> TokenStream2 {
     let token_definitions = parsed_variants.iter().map(|v| {
         let token_ident = &v.token_ident;
+        let ForwardedAttrs { cfg, docs, .. } = &v.attrs;
         quote! {
+            #(#cfg)* #(#docs)*
             #[derive(Default, Debug)]
             pub struct #token_ident;
         }
@@ -787,66 +1484,89 @@ fn generate_struct_from_conversions(
         .iter()
         .filter(|v| v.is_struct && v.struct_fields.is_some())
         .map(|v| {
-            let variant_ident = &v.variant_ident;
-            let struct_ident = format_ident!("{}{}Fields", enum_name, variant_ident);
-            let struct_ref_ident = format_ident!("{}{}Ref", enum_name, variant_ident);
-            let struct_mut_ident = format_ident!("{}{}Mut", enum_name, variant_ident);
+            let struct_ident = format_ident!("{}{}Fields", enum_name, v.variant_ident);
+            let struct_type = v.full_field_type.as_ref().unwrap();
+            let struct_ref_type = v.ref_type.as_ref().unwrap();
+            let struct_mut_type = v.mut_type.as_ref().unwrap();
+            let struct_generics = v.struct_generics.as_ref().unwrap();
+            let borrowed_generics = with_target_lifetime(struct_generics);
+            let (impl_generics, _, _) = borrowed_generics.split_for_impl();
 
             let fields = v.struct_fields.as_ref().unwrap();
+            let (_, _, struct_where_clause) = struct_generics.split_for_impl();
+
+            // Every field must be `Clone` for the owned conversions. The
+            // bounds are higher-ranked so that a non-`Clone` field type makes
+            // the conversions unavailable instead of failing the derive.
+            let clone_bounds: Vec<WherePredicate> = fields.iter().map(|field| {
+                let field_type = &field.ty;
+                parse_quote!(for<'__clone> #field_type: Clone)
+            }).collect();
+            let mut where_clause = struct_where_clause.cloned().unwrap_or_else(|| WhereClause {
+                where_token: Default::default(),
+                predicates: Default::default(),
+            });
+            where_clause.predicates.extend(clone_bounds.iter().cloned());
 
-            // Generate field conversions for Ref -> Fields
-            let ref_field_conversions: Vec<_> = fields.iter().map(|field| {
+            // Clone from reference - compiler optimizes Copy types automatically
+            let field_conversions: Vec<_> = fields.iter().map(|field| {
                 let field_name = field.ident.as_ref().unwrap();
-                // Clone from reference - compiler optimizes Copy types automatically
-                quote! { #field_name: src.#field_name.clone() }
+                quote! { #field_name: self.#field_name.clone() }
             }).collect();
 
-            // Generate field conversions for Mut -> Fields  
-            let mut_field_conversions: Vec<_> = fields.iter().map(|field| {
-                let field_name = field.ident.as_ref().unwrap();
-                // Clone from reference - compiler optimizes Copy types automatically
-                quote! { #field_name: src.#field_name.clone() }
-            }).collect();
+            v.scoped_items(quote! {
+                impl #impl_generics #struct_ref_type #struct_where_clause {
+                    /// Clones the referenced fields into an owned fields struct.
+                    pub fn to_owned_fields(&self) -> #struct_type
+                    where
+                        #(#clone_bounds),*
+                    {
+                        #struct_ident {
+                            #(#field_conversions),*
+                        }
+                    }
+                }
 
-            // The consuming conversions still need to dereference since Ref/Mut structs contain references
+                impl #impl_generics #struct_mut_type #struct_where_clause {
+                    /// Clones the referenced fields into an owned fields struct.
+                    pub fn to_owned_fields(&self) -> #struct_type
+                    where
+                        #(#clone_bounds),*
+                    {
+                        #struct_ident {
+                            #(#field_conversions),*
+                        }
+                    }
+                }
 
-            quote! {
                 // From conversion for Ref types
-                impl<'target> From<&'target #struct_ref_ident<'target>> for #struct_ident {
-                    fn from(src: &'target #struct_ref_ident<'target>) -> Self {
-                        Self {
-                            #(#ref_field_conversions),*
-                        }
+                impl #impl_generics From<&'target #struct_ref_type> for #struct_type #where_clause {
+                    fn from(src: &'target #struct_ref_type) -> Self {
+                        src.to_owned_fields()
                     }
                 }
 
                 // From conversion for Mut types
-                impl<'target> From<&'target #struct_mut_ident<'target>> for #struct_ident {
-                    fn from(src: &'target #struct_mut_ident<'target>) -> Self {
-                        Self {
-                            #(#mut_field_conversions),*
-                        }
+                impl #impl_generics From<&'target #struct_mut_type> for #struct_type #where_clause {
+                    fn from(src: &'target #struct_mut_type) -> Self {
+                        src.to_owned_fields()
                     }
                 }
 
                 // Direct From conversion for Ref types (consuming)
-                impl<'target> From<#struct_ref_ident<'target>> for #struct_ident {
-                    fn from(src: #struct_ref_ident<'target>) -> Self {
-                        Self {
-                            #(#ref_field_conversions),*
-                        }
+                impl #impl_generics From<#struct_ref_type> for #struct_type #where_clause {
+                    fn from(src: #struct_ref_type) -> Self {
+                        src.to_owned_fields()
                     }
                 }
 
                 // Direct From conversion for Mut types (consuming)
-                impl<'target> From<#struct_mut_ident<'target>> for #struct_ident {
-                    fn from(src: #struct_mut_ident<'target>) -> Self {
-                        Self {
-                            #(#mut_field_conversions),*
-                        }
+                impl #impl_generics From<#struct_mut_type> for #struct_type #where_clause {
+                    fn from(src: #struct_mut_type) -> Self {
+                        src.to_owned_fields()
                     }
                 }
-            }
+            })
         });
 
     quote! {
@@ -861,9 +1581,10 @@ fn generate_token_validation(
 ) -> TokenStream2 {
     let validation_checks = parsed_variants.iter().map(|v| {
         let token_ident = &v.token_ident;
+        let item_attrs = v.attrs.item_attrs();
         // This code runs at compile time inside the const block.
         // If the path #tokens_path::#token_ident is invalid, compilation fails here.
-        quote! { let _ = #tokens_path::#token_ident; }
+        quote! { #item_attrs let _ = #tokens_path::#token_ident; }
     });
 
     quote! {
@@ -876,10 +1597,44 @@ fn generate_token_validation(
     }
 }
 
+/// Generates a compile-time check that the variants of a mirrored remote enum
+/// cover the remote one, by matching it exhaustively.
+fn generate_remote_check(
+    remote: &Path,
+    generics: &Generics,
+    parsed_variants: &[ParsedVariantInfo],
+) -> TokenStream2 {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let patterns = parsed_variants.iter().map(|v| {
+        let variant_ident = &v.variant_ident;
+        let pattern = if v.is_unit {
+            quote! { #remote::#variant_ident }
+        } else if v.is_struct {
+            quote! { #remote::#variant_ident { .. } }
+        } else {
+            quote! { #remote::#variant_ident(_) }
+        };
+        let item_attrs = v.attrs.item_attrs();
+        quote! { #item_attrs #pattern }
+    });
+
+    quote! {
+        const _: () = {
+            #[allow(dead_code)]
+            fn check_remote_variants #impl_generics (value: &#remote #ty_generics) #where_clause {
+                match value {
+                    #(#patterns => {})*
+                }
+            }
+        };
+    }
+}
+
 /// Generates `impl #dtype_variant_path::EnumVariantTarget<...>` blocks.
 fn generate_enum_variant_downcast(
     dtype_variant_path: &Path,
     enum_name: &Ident,
+    enum_type: &Path,
     generics: &Generics,
     parsed_variants: &[ParsedVariantInfo],
     tokens_path: &Path, // Add tokens_path parameter
@@ -932,7 +1687,7 @@ fn generate_enum_variant_downcast(
 
                 let field_constructions: Vec<_> = fields.iter().map(|f| {
                     let field_name = f.ident.as_ref().unwrap();
-                    quote! { #field_name: #field_name.clone() }
+                    quote! { #field_name: #field_name }
                 }).collect();
 
                 let field_ref_constructions: Vec<_> = fields.iter().map(|f| {
@@ -985,14 +1740,14 @@ fn generate_enum_variant_downcast(
         };
 
         // Use the full path to the token
-        Some(if v.is_struct {
+        Some(v.scoped_items(if v.is_struct {
             // For struct variants, use the reference wrapper types
             let ref_target_type = v.ref_type.as_ref().unwrap();
             let mut_target_type = v.mut_type.as_ref().unwrap();
             quote! {
                 // Implement the #dtype_variant_path::EnumVariantDowncast trait for owned downcasting
                 impl #impl_generics #dtype_variant_path::EnumVariantDowncast<#tokens_path::#token_ident>
-                    for #enum_name #ty_generics #where_clause_with_bounds
+                    for #enum_type #ty_generics #where_clause_with_bounds
                 {
                     type Target = #full_field_type;
 
@@ -1006,9 +1761,9 @@ fn generate_enum_variant_downcast(
 
                 // Implement the #dtype_variant_path::EnumVariantDowncastRef trait for reference downcasting
                 impl #impl_generics #dtype_variant_path::EnumVariantDowncastRef<#tokens_path::#token_ident>
-                    for #enum_name #ty_generics #where_clause_with_bounds
+                    for #enum_type #ty_generics #where_clause_with_bounds
                 {
-                    type Target<'target> = #ref_target_type<'target> where Self: 'target;
+                    type Target<'target> = #ref_target_type where Self: 'target;
 
                     fn downcast_ref(&self) -> Option<Self::Target<'_>> {
                         match self {
@@ -1020,9 +1775,9 @@ fn generate_enum_variant_downcast(
 
                 // Implement the #dtype_variant_path::EnumVariantDowncastMut trait for mutable reference downcasting
                 impl #impl_generics #dtype_variant_path::EnumVariantDowncastMut<#tokens_path::#token_ident>
-                    for #enum_name #ty_generics #where_clause_with_bounds
+                    for #enum_type #ty_generics #where_clause_with_bounds
                 {
-                    type Target<'target> = #mut_target_type<'target> where Self: 'target;
+                    type Target<'target> = #mut_target_type where Self: 'target;
 
                     fn downcast_mut(&mut self) -> Option<Self::Target<'_>> {
                         match self {
@@ -1037,7 +1792,7 @@ fn generate_enum_variant_downcast(
             quote! {
                 // Implement the #dtype_variant_path::EnumVariantDowncast trait for owned downcasting
                 impl #impl_generics #dtype_variant_path::EnumVariantDowncast<#tokens_path::#token_ident>
-                    for #enum_name #ty_generics #where_clause_with_bounds
+                    for #enum_type #ty_generics #where_clause_with_bounds
                 {
                     type Target = #full_field_type;
 
@@ -1051,7 +1806,7 @@ fn generate_enum_variant_downcast(
 
                 // Implement the #dtype_variant_path::EnumVariantDowncastRef trait for reference downcasting
                 impl #impl_generics #dtype_variant_path::EnumVariantDowncastRef<#tokens_path::#token_ident>
-                    for #enum_name #ty_generics #where_clause_with_bounds
+                    for #enum_type #ty_generics #where_clause_with_bounds
                 {
                     type Target<'target> = &'target #full_field_type where Self: 'target;
 
@@ -1065,7 +1820,7 @@ fn generate_enum_variant_downcast(
 
                 // Implement the #dtype_variant_path::EnumVariantDowncastMut trait for mutable reference downcasting
                 impl #impl_generics #dtype_variant_path::EnumVariantDowncastMut<#tokens_path::#token_ident>
-                    for #enum_name #ty_generics #where_clause_with_bounds
+                    for #enum_type #ty_generics #where_clause_with_bounds
                 {
                     type Target<'target> = &'target mut #full_field_type where Self: 'target;
 
@@ -1077,7 +1832,7 @@ fn generate_enum_variant_downcast(
                     }
                 }
             }
-        })
+        }))
     });
 
     quote! {
@@ -1085,28 +1840,80 @@ fn generate_enum_variant_downcast(
     }
 }
 
-/// Generates `impl #dtype_variant_path::EnumVariantConstraint<...>` blocks if `constraint` is specified.
+/// Generates `impl #dtype_variant_path::EnumVariantConstruct<...>` blocks for every variant.
+fn generate_enum_variant_construct(
+    dtype_variant_path: &Path,
+    enum_type: &Path,
+    generics: &Generics,
+    parsed_variants: &[ParsedVariantInfo],
+    tokens_path: &Path,
+) -> TokenStream2 {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let construct_impls = parsed_variants.iter().map(|v| {
+        let variant_ident = &v.variant_ident;
+        let token_ident = &v.token_ident;
+
+        // Unit variants are built from `()`, struct variants from their `Fields` struct
+        let (target_type, construction) = match (&v.full_field_type, &v.struct_fields) {
+            (Some(full_field_type), Some(fields)) => {
+                let field_names = fields.iter().filter_map(|f| f.ident.as_ref());
+                (
+                    quote! { #full_field_type },
+                    quote! { Self::#variant_ident { #(#field_names: payload.#field_names),* } },
+                )
+            }
+            (Some(full_field_type), None) => {
+                (quote! { #full_field_type }, quote! { Self::#variant_ident(payload) })
+            }
+            _ => (quote! { () }, quote! { Self::#variant_ident }),
+        };
+
+        v.scoped_items(quote! {
+            impl #impl_generics #dtype_variant_path::EnumVariantConstruct<#tokens_path::#token_ident>
+                for #enum_type #ty_generics #where_clause
+            {
+                type Target = #target_type;
+
+                #[allow(unused_variables)]
+                fn construct(payload: Self::Target) -> Self {
+                    #construction
+                }
+            }
+        })
+    });
+
+    quote! { #(#construct_impls)* }
+}
+
+/// Generates `impl #dtype_variant_path::EnumVariantConstraint<...>` blocks if a `constraint` is specified.
+///
+/// Once the enum or any of its variants has a constraint, every variant gets
+/// an impl so that generic code over all tokens type-checks: payload variants
+/// constrain their inner type by their own or the enum's bounds (if any), and
+/// unit variants fall back to `type Constraint = ()`.
 fn generate_enum_variant_constraint(
     dtype_variant_path: &Path,
-    enum_name: &Ident,
+    enum_type: &Path,
     generics: &Generics,
     parsed_variants: &[ParsedVariantInfo],
-    constraint_opt: &Option<Expr>,
+    constraint_opt: &Option<ConstraintBounds>,
     tokens_path: &Path, // Add tokens_path parameter
 ) -> TokenStream2 {
-    let constraint = match constraint_opt {
-        Some(path) => path,
-        None => return quote! {}, // No constraint specified
-    };
+    if constraint_opt.is_none()
+        && parsed_variants.iter().all(|v| v.constraint.is_none())
+    {
+        return quote! {}; // No constraint specified
+    }
 
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
-    let constraint_impls = parsed_variants.iter().filter_map(|v| {
-        if v.is_unit {
-            return None;
-        } // Skip unit variants
+    let constraint_impls = parsed_variants.iter().map(|v| {
         let token_ident = &v.token_ident;
-        let inner_type = v.inner_type.as_ref()?;
+        let constraint_type = match &v.inner_type {
+            Some(inner_type) => quote! { #inner_type },
+            None => quote! { () },
+        };
 
         // Add the constraint bound to the where clause
         let mut where_clause_with_bounds = where_clause.cloned().unwrap_or_else(|| WhereClause {
@@ -1114,19 +1921,21 @@ fn generate_enum_variant_constraint(
             predicates: Default::default(),
         });
 
-        // Add constraint that inner_type implements the constraint_path
-        where_clause_with_bounds
-            .predicates
-            .push(parse_quote!(#inner_type: #constraint));
+        // Add constraint that inner_type satisfies the variant's or the enum's bounds
+        if let Some(constraint) = v.constraint.as_ref().or(constraint_opt.as_ref()).filter(|_| !v.is_unit) {
+            where_clause_with_bounds
+                .predicates
+                .push(parse_quote!(#constraint_type: #constraint));
+        }
 
-        Some(quote! {
+        v.scoped_items(quote! {
             // Implement #dtype_variant_path::EnumVariantConstraint for the enum with the token type
             // Use the full path to the token
             impl #impl_generics #dtype_variant_path::EnumVariantConstraint<#tokens_path::#token_ident>
-                for #enum_name #ty_generics #where_clause_with_bounds
+                for #enum_type #ty_generics #where_clause_with_bounds
             {
                 // Set the Constraint associated type to the inner type
-                type Constraint = #inner_type;
+                type Constraint = #constraint_type;
             }
         })
     });
@@ -1134,9 +1943,251 @@ fn generate_enum_variant_constraint(
     quote! { #(#constraint_impls)* }
 }
 
+/// With a `container`, generates a `{Enum}Container` marker implementing
+/// `PayloadContainer` to name it at type level.
+fn generate_container_marker(
+    dtype_variant_path: &Path,
+    enum_name: &Ident,
+    container_ident: &Option<Ident>,
+    vis: &Visibility,
+) -> Option<(Ident, TokenStream2)> {
+    container_ident.as_ref().map(|container| {
+        let marker_ident = format_ident!("{}Container", enum_name);
+        let container_definition = quote! {
+            /// Marker naming the container wrapping the payloads of the enum.
+            #vis struct #marker_ident;
+
+            impl #dtype_variant_path::PayloadContainer for #marker_ident {
+                type Wrap<T> = #container<T>;
+            }
+        };
+        (marker_ident, container_definition)
+    })
+}
+
+/// Generates `impl #dtype_variant_path::VariantPayload<...>` blocks for every variant.
+fn generate_variant_payload_impls(
+    dtype_variant_path: &Path,
+    enum_type: &Path,
+    generics: &Generics,
+    parsed_variants: &[ParsedVariantInfo],
+    container_marker: &Option<(Ident, TokenStream2)>,
+    tokens_path: &Path,
+) -> TokenStream2 {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let payload_impls = parsed_variants.iter().map(|v| {
+        let token_ident = &v.token_ident;
+        let (full_type, inner_type) = match (&v.full_field_type, &v.inner_type) {
+            (Some(full_type), Some(inner_type)) => (quote! { #full_type }, quote! { #inner_type }),
+            _ => (quote! { () }, quote! { () }),
+        };
+        // Struct and unit variants are never wrapped in the container
+        let container_type = match container_marker {
+            Some((marker_ident, _)) if !v.is_unit && !v.is_struct => quote! { #marker_ident },
+            _ => quote! { #dtype_variant_path::NoContainer },
+        };
+
+        v.scoped_items(quote! {
+            impl #impl_generics #dtype_variant_path::VariantPayload<#tokens_path::#token_ident>
+                for #enum_type #ty_generics #where_clause
+            {
+                type Full = #full_type;
+                type Inner = #inner_type;
+                type Container = #container_type;
+            }
+        })
+    });
+
+    let container_definition =
+        container_marker.as_ref().map(|(_, definition)| definition);
+    quote! {
+        #container_definition
+        #(#payload_impls)*
+    }
+}
+
+/// Generates the impls of the `delegate` traits, forwarding their methods to
+/// the payload of the active variant.
+///
+/// The formatting traits are expanded here. Other traits are expanded by the
+/// macro `#[dtype_delegate]` defines next to them, which knows their methods.
+/// Payloads mentioning type parameters of the enum get a bound on the trait.
+fn generate_delegate_impls(
+    dtype_variant_path: &Path,
+    enum_type: &Path,
+    generics: &Generics,
+    parsed_variants: &[ParsedVariantInfo],
+    delegates: &[Path],
+) -> Result<TokenStream2, Error> {
+    if delegates.is_empty() {
+        return Ok(TokenStream2::new());
+    }
+
+    let mut variants = Vec::new();
+    for v in parsed_variants {
+        let ty = match &v.full_field_type {
+            Some(ty) if !v.is_struct => ty.clone(),
+            _ => {
+                return Err(Error::new_spanned(
+                    &v.variant_ident,
+                    format!(
+                        "`delegate` forwards to variant payloads, but `{}` is \
+                         a {} variant",
+                        v.variant_ident,
+                        if v.is_unit { "unit" } else { "struct" }
+                    ),
+                ));
+            }
+        };
+        variants.push(DelegateVariant {
+            attrs: Attribute::parse_outer.parse2(v.attrs.item_attrs())?,
+            ident: v.variant_ident.clone(),
+            ty,
+        });
+    }
+
+    let type_params: IndexSet<String> = generics
+        .type_params()
+        .map(|param| param.ident.to_string())
+        .collect();
+    let mentions_type_param = |ty: &Type| {
+        let mut idents = IndexSet::new();
+        collect_names(quote!(#ty), &mut idents, &mut IndexSet::new());
+        !idents.is_disjoint(&type_params)
+    };
+
+    let mut impls = TokenStream2::new();
+    for trait_path in delegates {
+        let mut bounded = generics.clone();
+        let where_clause = bounded.make_where_clause();
+        for DelegateVariant { ty, .. } in &variants {
+            if mentions_type_param(ty) {
+                where_clause.predicates.push(parse_quote!(#ty: #trait_path));
+            }
+        }
+        let (impl_generics, ty_generics, where_clause) =
+            bounded.split_for_impl();
+        let item_impl: syn::ItemImpl = parse_quote! {
+            impl #impl_generics #trait_path for #enum_type #ty_generics
+                #where_clause {}
+        };
+
+        match builtin_trait(trait_path) {
+            Some(trait_def) => {
+                impls.extend(expand(&trait_def, item_impl, &variants)?)
+            }
+            None => {
+                let macro_path = macro_path(trait_path);
+                impls.extend(quote! {
+                    #macro_path! {
+                        { #dtype_variant_path }
+                        #item_impl
+                        { #(#variants),* }
+                    }
+                });
+            }
+        }
+    }
+    Ok(impls)
+}
+
+/// Generates `impl #dtype_variant_path::PayloadToken<...>` blocks mapping payload types to tokens.
+///
+/// Types shared by several variants have no unique token, so their impl is
+/// bounded on `UniquePayload`, which is never implemented and reports the
+/// carrying variants. Types mentioning type parameters of the enum are
+/// skipped, as their impls could overlap (generated `Fields` structs never
+/// do). For a remote enum,
+/// only the local `Fields` structs are mapped, since impls for its (usually
+/// foreign) payload types would break the orphan rule.
+fn generate_payload_token_impls(
+    dtype_variant_path: &Path,
+    enum_type: &Path,
+    generics: &Generics,
+    parsed_variants: &[ParsedVariantInfo],
+    tokens_path: &Path,
+    is_remote: bool,
+) -> TokenStream2 {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let type_params: IndexSet<String> = generics
+        .type_params()
+        .map(|param| param.ident.to_string())
+        .collect();
+    let mentions_type_param = |ty: &Type| {
+        let mut idents = IndexSet::new();
+        collect_names(quote!(#ty), &mut idents, &mut IndexSet::new());
+        !idents.is_disjoint(&type_params)
+    };
+
+    // Payload type (by its tokens) -> the variants carrying it
+    let mut payload_types: IndexMap<String, (&Type, Vec<&ParsedVariantInfo>)> =
+        IndexMap::new();
+    let variants = parsed_variants
+        .iter()
+        .filter(|v| !v.is_unit && (v.is_struct || !is_remote));
+    for v in variants {
+        let candidates = v.inner_type.iter().chain(&v.full_field_type);
+        for ty in candidates {
+            if !v.is_struct && mentions_type_param(ty) {
+                continue;
+            }
+            let (_, carriers) = payload_types
+                .entry(quote!(#ty).to_string())
+                .or_insert_with(|| (ty, Vec::new()));
+            if !carriers.iter().any(|c| c.token_ident == v.token_ident) {
+                carriers.push(v);
+            }
+        }
+    }
+
+    let payload_token_impls = payload_types.values().map(|(ty, carriers)| {
+        if let [v] = carriers.as_slice() {
+            let token_ident = &v.token_ident;
+            return v.scoped_items(quote! {
+                impl #impl_generics #dtype_variant_path::PayloadToken<#ty>
+                    for #enum_type #ty_generics #where_clause
+                {
+                    type Token = #tokens_path::#token_ident;
+                }
+            });
+        }
+
+        // A shared type gets an impl that is never usable, so that looking
+        // it up names the variants carrying it.
+        let token_idents = carriers.iter().map(|v| &v.token_ident);
+        let first_token = &carriers[0].token_ident;
+        let attrs = carriers.iter().map(|v| v.attrs.item_attrs());
+        let mut where_clause_with_bounds =
+            where_clause.cloned().unwrap_or_else(|| WhereClause {
+                where_token: Default::default(),
+                predicates: Default::default(),
+            });
+        where_clause_with_bounds.predicates.push(parse_quote! {
+            for<'__shared> #enum_type #ty_generics: #dtype_variant_path::UniquePayload<
+                #ty,
+                (#(#tokens_path::#token_idents,)*),
+            >
+        });
+        quote! {
+            #(#attrs)*
+            const _: () = {
+                impl #impl_generics #dtype_variant_path::PayloadToken<#ty>
+                    for #enum_type #ty_generics #where_clause_with_bounds
+                {
+                    type Token = #tokens_path::#first_token;
+                }
+            };
+        }
+    });
+
+    quote! { #(#payload_token_impls)* }
+}
+
 /// Generates `impl From<FieldType>` blocks.
 fn generate_from_impls(
-    enum_name: &Ident,
+    enum_type: &Path,
     generics: &Generics,
     parsed_variants: &[ParsedVariantInfo],
     has_container: bool,
@@ -1146,9 +2197,9 @@ fn generate_from_impls(
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
     let payload_from_impls = parsed_variants.iter().filter_map(|v| {
-        if v.is_unit {
+        if v.is_unit || !has_from_impl(v, skip_from_impls) {
             return None;
-        } // Skip unit variants
+        } // Skip unit variants and variants opting out of From
         let variant_ident = &v.variant_ident;
         let full_field_type = v.full_field_type.as_ref()?; // Type in the variant tuple, e.g., Vec<u16> or f64
         let inner_type = v.inner_type.as_ref()?; // Inner type, e.g., u16 or f64
@@ -1161,35 +2212,35 @@ fn generate_from_impls(
                     let field_name = f.ident.as_ref().unwrap();
                     quote! { #field_name: value.#field_name }
                 }).collect();
-                Some(quote! {
-                    impl #impl_generics From<#full_field_type> for #enum_name #ty_generics #where_clause {
+                Some(v.scoped_items(quote! {
+                    impl #impl_generics From<#full_field_type> for #enum_type #ty_generics #where_clause {
                         fn from(value: #full_field_type) -> Self {
                             Self::#variant_ident { #(#field_constructions),* }
                         }
                     }
-                })
+                }))
             } else {
                 None
             }
         } else if has_container {
             // When container is used, generate From<FullFieldType> -> EnumName
-            Some(quote! {
-                impl #impl_generics From<#full_field_type> for #enum_name #ty_generics #where_clause {
+            Some(v.scoped_items(quote! {
+                impl #impl_generics From<#full_field_type> for #enum_type #ty_generics #where_clause {
                     fn from(value: #full_field_type) -> Self {
                         Self::#variant_ident(value)
                     }
                 }
-            })
+            }))
         } else {
             // When no container is used, full_field_type and inner_type are the same
             // So only generate one implementation
-            Some(quote! {
-                impl #impl_generics From<#inner_type> for #enum_name #ty_generics #where_clause {
+            Some(v.scoped_items(quote! {
+                impl #impl_generics From<#inner_type> for #enum_type #ty_generics #where_clause {
                     fn from(value: #inner_type) -> Self {
                         Self::#variant_ident(value)
                     }
                 }
-            })
+            }))
         }
     });
 
@@ -1201,47 +2252,79 @@ fn generate_from_impls(
         let variant_ident = &v.variant_ident;
         let token_ident = &v.token_ident;
 
-        Some(quote! {
-            impl #impl_generics From<#tokens_path::#token_ident> for #enum_name #ty_generics #where_clause {
+        Some(v.scoped_items(quote! {
+            impl #impl_generics From<#tokens_path::#token_ident> for #enum_type #ty_generics #where_clause {
                 fn from(_: #tokens_path::#token_ident) -> Self {
                     Self::#variant_ident
                 }
             }
-        })
+        }))
     });
 
-    // Check if all variants are unit types
-    let all_unit_variants = parsed_variants.iter().all(|v| v.is_unit);
+    quote! {
+        #(#payload_from_impls)*
+        #(#unit_from_impls)*
+    }
+}
+
+/// Generates `from_variant` and `of_payload` for enums whose variants are all
+/// unit variants, and `of` if they name the enum carrying the payloads.
+fn generate_from_variant_methods(
+    dtype_variant_path: &Path,
+    enum_name: &Ident,
+    generics: &Generics,
+    parsed_variants: &[ParsedVariantInfo],
+    payloads: Option<&Path>,
+) -> TokenStream2 {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // Only generated if all variants are unit types
+    if !parsed_variants.iter().all(|v| v.is_unit) {
+        return quote! {};
+    }
 
-    // Generate from_variant implementation if all variants are unit types
-    let from_variant_impl = if all_unit_variants {
+    let of_method = payloads.map(|payloads| {
         quote! {
-            impl #impl_generics #enum_name #ty_generics #where_clause {
-                /// Creates a new instance of the enum from a variant token.
-                /// The variant token must implement Default.
-                pub fn from_variant<V>() -> Self
-                where
-                    Self: From<V>,
-                    V: Default,
-                {
-                    Self::from(V::default())
-                }
+            /// Creates the variant for the token the `payloads` enum maps the payload
+            /// type `T` to, e.g. `Kind::of::<i32>()`.
+            pub fn of<T>() -> Self
+            where
+                #payloads: #dtype_variant_path::PayloadToken<T>,
+                Self: From<<#payloads as #dtype_variant_path::PayloadToken<T>>::Token>,
+                <#payloads as #dtype_variant_path::PayloadToken<T>>::Token: Default,
+            {
+                Self::of_payload::<T, #payloads>()
             }
         }
-    } else {
-        quote! {}
-    };
+    });
 
-    if skip_from_impls {
-        quote! {
-            #from_variant_impl
-            #(#unit_from_impls)*
-        }
-    } else {
-        quote! {
-            #(#payload_from_impls)*
-            #(#unit_from_impls)*
-            #from_variant_impl
+    quote! {
+        impl #impl_generics #enum_name #ty_generics #where_clause {
+            /// Creates a new instance of the enum from a variant token.
+            /// The variant token must implement Default.
+            pub fn from_variant<V>() -> Self
+            where
+                Self: From<V>,
+                V: Default,
+            {
+                Self::from(V::default())
+            }
+
+            /// Creates the variant for the token `Source` maps the payload type `T` to,
+            /// e.g. `Kind::of_payload::<i32, DynChunk>()`.
+            ///
+            /// For kinds whose tokens are shared by several enums carrying payloads;
+            /// with `payloads = Source`, `Kind::of::<T>()` maps through `Source`.
+            pub fn of_payload<T, Source>() -> Self
+            where
+                Source: #dtype_variant_path::PayloadToken<T>,
+                Self: From<<Source as #dtype_variant_path::PayloadToken<T>>::Token>,
+                <Source as #dtype_variant_path::PayloadToken<T>>::Token: Default,
+            {
+                Self::from_variant::<<Source as #dtype_variant_path::PayloadToken<T>>::Token>()
+            }
+
+            #of_method
         }
     }
 }
@@ -1285,241 +2368,705 @@ fn generate_downcast_methods(
             {
                 <Self as #dtype_variant_path::EnumVariantDowncast<Token>>::downcast(self)
             }
+
+            /// Downcasts every element of `items` to the variant corresponding to token
+            /// type `Token`, failing on the first element holding another variant.
+            pub fn downcast_all<Token>(items: &[Self]) -> Result<Vec<<Self as #dtype_variant_path::EnumVariantDowncastRef<Token>>::Target<'_>>, #dtype_variant_path::VariantMismatch>
+            where
+                Self: #dtype_variant_path::EnumVariantDowncastRef<Token>
+            {
+                #dtype_variant_path::downcast_all::<Token, Self>(items)
+            }
+
+            /// Creates the variant corresponding to token type `Token` from its payload.
+            pub fn new<Token>(payload: <Self as #dtype_variant_path::EnumVariantConstruct<Token>>::Target) -> Self
+            where
+                Self: #dtype_variant_path::EnumVariantConstruct<Token>
+            {
+                <Self as #dtype_variant_path::EnumVariantConstruct<Token>>::construct(payload)
+            }
+
+            /// Creates the variant corresponding to token type `Token` with a default payload.
+            pub fn default_of<Token>() -> Self
+            where
+                Self: #dtype_variant_path::EnumVariantConstruct<Token>,
+                <Self as #dtype_variant_path::EnumVariantConstruct<Token>>::Target: Default
+            {
+                Self::new::<Token>(Default::default())
+            }
+
+            /// Replaces `self` with the variant corresponding to token type `Token`,
+            /// built from `payload`, and returns the previous value.
+            pub fn set<Token>(&mut self, payload: <Self as #dtype_variant_path::EnumVariantConstruct<Token>>::Target) -> Self
+            where
+                Self: #dtype_variant_path::EnumVariantConstruct<Token>
+            {
+                ::core::mem::replace(self, Self::new::<Token>(payload))
+            }
+
+            /// Takes the payload out if the enum holds the variant corresponding to
+            /// token type `Token`, leaving that variant with a default payload.
+            /// Returns `None`, leaving the enum untouched, for other variants.
+            pub fn take_as<Token>(&mut self) -> Option<<Self as #dtype_variant_path::EnumVariantDowncast<Token>>::Target>
+            where
+                Self: #dtype_variant_path::EnumVariantDowncast<Token>
+                    + #dtype_variant_path::EnumVariantDowncastRef<Token>
+                    + #dtype_variant_path::EnumVariantConstruct<Token>,
+                <Self as #dtype_variant_path::EnumVariantConstruct<Token>>::Target: Default
+            {
+                self.downcast_ref::<Token>()?;
+                ::core::mem::replace(self, Self::default_of::<Token>()).downcast::<Token>()
+            }
+
+            /// Transforms the payload with `f` if the enum holds the variant
+            /// corresponding to token type `Token`; other variants are returned as is.
+            pub fn map_variant<Token, F>(self, f: F) -> Self
+            where
+                Self: #dtype_variant_path::EnumVariantDowncast<Token>
+                    + #dtype_variant_path::EnumVariantDowncastRef<Token>
+                    + #dtype_variant_path::EnumVariantConstruct<Token>,
+                F: FnOnce(
+                    <Self as #dtype_variant_path::EnumVariantDowncast<Token>>::Target,
+                ) -> <Self as #dtype_variant_path::EnumVariantConstruct<Token>>::Target
+            {
+                if self.downcast_ref::<Token>().is_none() {
+                    return self;
+                }
+                match self.downcast::<Token>() {
+                    Some(payload) => Self::new::<Token>(f(payload)),
+                    None => unreachable!("`downcast_ref` found the variant"),
+                }
+            }
         }
     }
 }
 
-/// Generates a macro for pattern matching on enum variants if `matcher` name is provided.
-/// **Uses `generate_match_arm_content`**.
-fn generate_matcher_method(
+/// Generates the `accessors`: `is_{variant}` for every variant, and
+/// `as_{variant}`, `as_{variant}_mut` and `into_{variant}` for variants with
+/// a payload, forwarding to the `EnumVariantDowncast*` impls.
+fn generate_accessor_methods(
     dtype_variant_path: &Path,
     enum_name: &Ident,
-    _generics: &Generics, // Keep signature consistent, used by path closures
+    generics: &Generics,
     parsed_variants: &[ParsedVariantInfo],
-    matcher_ident: &Option<Ident>,
     tokens_path: &Path,
 ) -> TokenStream2 {
-    let matcher_name = match matcher_ident {
-        Some(ident) => ident,
-        None => return quote! {}, // No matcher name specified
-    };
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
-    let all_unit_variants = parsed_variants.iter().all(|v| v.is_unit);
-    let internal_matcher_name = format_ident!("_{}", matcher_name);
+    let accessors = parsed_variants.iter().map(|v| {
+        let variant_ident = &v.variant_ident;
+        let token_ident = &v.token_ident;
+        let token = quote! { #tokens_path::#token_ident };
+        let name = to_snake_case(&variant_ident.unraw().to_string());
+        let is_ident = format_ident!("is_{}", name);
+        let as_ident = format_ident!("as_{}", name);
+        let as_mut_ident = format_ident!("as_{}_mut", name);
+        let into_ident = format_ident!("into_{}", name);
+        let item_attrs = v.attrs.item_attrs();
+
+        let pattern = if v.is_unit {
+            quote! { Self::#variant_ident }
+        } else if v.is_struct {
+            quote! { Self::#variant_ident { .. } }
+        } else {
+            quote! { Self::#variant_ident(_) }
+        };
+        let is_method = quote! {
+            #[doc = concat!("Returns `true` if this is a `", stringify!(#variant_ident), "`.")]
+            #item_attrs
+            pub fn #is_ident(&self) -> bool {
+                matches!(self, #pattern)
+            }
+        };
+        if v.is_unit {
+            return is_method;
+        }
 
-    // Generate macro-compatible paths
-    let (tokens_path, dtype_variant_path) =
-        generate_macro_compatible_paths(tokens_path, dtype_variant_path);
+        quote! {
+            #is_method
 
-    let generate_macro_rule_arm = generate_macro_rule_arm(
-        enum_name,
-        parsed_variants,
-        tokens_path.clone(),
-        &dtype_variant_path,
-        None,
-    );
+            #[doc = concat!("Returns a reference to the payload of a `", stringify!(#variant_ident), "`,")]
+            /// like `downcast_ref`.
+            #item_attrs
+            pub fn #as_ident(&self) -> Option<<Self as #dtype_variant_path::EnumVariantDowncastRef<#token>>::Target<'_>> {
+                <Self as #dtype_variant_path::EnumVariantDowncastRef<#token>>::downcast_ref(self)
+            }
 
-    // Generate all combinations of macro arms (same logic as before, calling the new generate_macro_arms)
-    let macro_rule_arms = if all_unit_variants {
-        vec![
-            generate_macro_rule_arm(false, false, false, false, false, false),
-            generate_macro_rule_arm(false, false, false, true, false, false),
-            generate_macro_rule_arm(false, false, false, true, true, false),
-            generate_macro_rule_arm(false, false, false, true, false, true),
-            generate_macro_rule_arm(false, false, false, true, true, true),
-        ]
-    } else {
-        vec![
-            generate_macro_rule_arm(false, false, false, false, false, false),
-            generate_macro_rule_arm(false, false, false, true, false, false),
-            generate_macro_rule_arm(false, false, false, true, false, true),
-            generate_macro_rule_arm(false, false, false, true, true, false),
-            generate_macro_rule_arm(false, false, false, true, true, true),
-            generate_macro_rule_arm(false, true, false, false, false, false),
-            generate_macro_rule_arm(false, true, false, true, false, false),
-            generate_macro_rule_arm(false, true, false, true, false, true),
-            generate_macro_rule_arm(false, true, false, true, true, false),
-            generate_macro_rule_arm(false, true, false, true, true, true),
-            generate_macro_rule_arm(false, true, true, false, false, false),
-            generate_macro_rule_arm(false, true, true, true, false, false),
-            generate_macro_rule_arm(false, true, true, true, false, true),
-            generate_macro_rule_arm(false, true, true, true, true, true),
-            generate_macro_rule_arm(true, true, false, false, false, false),
-            generate_macro_rule_arm(true, true, false, true, false, false),
-            generate_macro_rule_arm(true, true, false, true, false, true),
-            generate_macro_rule_arm(true, true, false, true, true, false),
-            generate_macro_rule_arm(true, true, false, true, true, true),
-            generate_macro_rule_arm(true, true, true, false, false, false),
-            generate_macro_rule_arm(true, true, true, true, false, false),
-            generate_macro_rule_arm(true, true, true, true, false, true),
-            generate_macro_rule_arm(true, true, true, true, true, false),
-            generate_macro_rule_arm(true, true, true, true, true, true),
-        ]
-    };
+            #[doc = concat!("Returns a mutable reference to the payload of a `", stringify!(#variant_ident), "`,")]
+            /// like `downcast_mut`.
+            #item_attrs
+            pub fn #as_mut_ident(&mut self) -> Option<<Self as #dtype_variant_path::EnumVariantDowncastMut<#token>>::Target<'_>> {
+                <Self as #dtype_variant_path::EnumVariantDowncastMut<#token>>::downcast_mut(self)
+            }
 
-    let macro_arms = macro_rule_arms
-        .into_iter()
-        .map(
-            |MacroRuleArm {
-                 pattern_prefix_fragment,
-                 pattern_suffix_fragment,
-                 variant_bodies,
-             }| {
-                quote! {
-                    ($value:expr, #pattern_prefix_fragment #pattern_suffix_fragment) => {
-                        match $value {
-                            #variant_bodies
-                        }
-                    };
-                }
-            },
-        )
-        .collect::<Vec<_>>()
-        .into_iter()
-        .fold(TokenStream2::new(), |mut acc, arm| {
-            acc.extend(arm);
-            acc
-        });
+            #[doc = concat!("Returns the payload of a `", stringify!(#variant_ident), "`, like `downcast`.")]
+            #item_attrs
+            pub fn #into_ident(self) -> Option<<Self as #dtype_variant_path::EnumVariantDowncast<#token>>::Target> {
+                <Self as #dtype_variant_path::EnumVariantDowncast<#token>>::downcast(self)
+            }
+        }
+    });
 
-    // --- Final Macro Definition ---
     quote! {
-            #[doc(hidden)]
-            #[macro_export]
-            macro_rules! #internal_matcher_name {
-                #macro_arms
+        impl #impl_generics #enum_name #ty_generics #where_clause {
+            #(#accessors)*
+        }
+    }
+}
+
+/// Converts a `PascalCase` variant name to `snake_case`, keeping acronyms
+/// together (`HTTPRequest` becomes `http_request`).
+fn to_snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_is_lower =
+                chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev.is_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_uppercase() && next_is_lower)
+            {
+                snake.push('_');
             }
-            #[allow(unused_imports)]
-            pub use #internal_matcher_name as #matcher_name;
+        }
+        snake.extend(c.to_lowercase());
     }
+    snake
 }
 
-/// Generates the grouped matcher macro if `grouped_matcher` is specified.
-/// **Uses `generate_match_arm_content`**. Does NOT reuse `generate_macro_arms`.
-/// Generates the grouped matcher macro if `grouped_matcher` is specified.
-/// **Uses `generate_match_arm_content`**. Does NOT reuse `generate_macro_arms`.
-fn generate_grouped_matcher_macro(
+/// Generates the `buckets` struct, which stores the payloads of each variant
+/// in a `Vec` of their own, plus the variant index of every pushed value to
+/// restore the insertion order.
+#[allow(clippy::too_many_arguments)]
+fn generate_buckets(
     dtype_variant_path: &Path,
     enum_name: &Ident,
-    _generics: &Generics, // Keep signature consistent
+    enum_type: &Path,
+    buckets_ident: &Ident,
+    vis: &Visibility,
+    generics: &Generics,
     parsed_variants: &[ParsedVariantInfo],
-    parsed_grouped_matcher: &ParsedGroupedMatcher,
     tokens_path: &Path,
 ) -> TokenStream2 {
-    let macro_name = &parsed_grouped_matcher.macro_name;
-    let groups = &parsed_grouped_matcher.groups;
-    let internal_macro_name = format_ident!("_{}", macro_name);
-
-    // Build maps for quick lookup: VariantIdent String -> VariantInfo and VariantIdent String -> Group Index
-    let mut variant_info_map: IndexMap<String, &ParsedVariantInfo> =
-        IndexMap::new();
-    let mut variant_to_group_index: IndexMap<String, usize> = IndexMap::new();
-    for (group_index, (_, group_variants)) in groups.iter().enumerate() {
-        for variant_ident in group_variants {
-            variant_to_group_index
-                .insert(variant_ident.to_string(), group_index);
-        }
-    }
-    for v in parsed_variants {
-        variant_info_map.insert(v.variant_ident.to_string(), v);
-    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
-    // Generate macro-compatible paths
-    let (tokens_path, dtype_variant_path) =
-        generate_macro_compatible_paths(tokens_path, dtype_variant_path);
+    let fields: Vec<Ident> = parsed_variants
+        .iter()
+        .map(|v| {
+            let name = to_snake_case(&v.variant_ident.unraw().to_string());
+            // Keywords such as `type` or `move` get a trailing underscore.
+            syn::parse_str::<Ident>(&name)
+                .unwrap_or_else(|_| format_ident!("{}_", name))
+        })
+        .collect();
+    let payloads: Vec<TokenStream2> = parsed_variants
+        .iter()
+        .map(|v| match &v.full_field_type {
+            Some(ty) => quote! { #ty },
+            None => quote! { () },
+        })
+        .collect();
+    let attrs: Vec<TokenStream2> = parsed_variants
+        .iter()
+        .map(|v| v.attrs.item_attrs())
+        .collect();
+    let constructs: Vec<TokenStream2> = parsed_variants
+        .iter()
+        .map(|v| {
+            let token_ident = &v.token_ident;
+            quote! {
+                <#enum_type #ty_generics as #dtype_variant_path::EnumVariantConstruct<#tokens_path::#token_ident>>::construct
+            }
+        })
+        .collect();
+    // The order index stores `u32`s rather than `usize`s to save memory.
+    let indices: Vec<u32> = (0..parsed_variants.len() as u32).collect();
+    let variant_count = parsed_variants.len();
+    let allow_deprecated = parsed_variants
+        .iter()
+        .any(|v| v.attrs.deprecated)
+        .then(|| quote! { #[allow(deprecated)] });
 
-    // --- Define the Macro Rule ---
-    let create_group_macro_arm = |include_src_ty: bool| {
-        // Captures `macro!(value, [V1, V2] (inner) => { body0 }, [V3] => { body1 })`
-        let group_pattern_arms = groups
-            .iter()
-            .enumerate()
-            .map(|(group_index, (group_name, group_variants))| {
-                let group_variants: Vec<ParsedVariantInfo> = group_variants
-                    .iter()
-                    .map(|ident| variant_info_map[&ident.to_string()])
-                    .cloned()
-                    .collect();
+    let push_arms = parsed_variants.iter().zip(&fields).zip(&indices).map(
+        |((v, field), index)| {
+            let variant_ident = &v.variant_ident;
+            let item_attrs = v.attrs.item_attrs();
+            let (pattern, payload) = match &v.struct_fields {
+                Some(struct_fields) => {
+                    let names: Vec<_> = struct_fields
+                        .iter()
+                        .filter_map(|f| f.ident.as_ref())
+                        .collect();
+                    let struct_ident =
+                        format_ident!("{}{}Fields", enum_name, variant_ident);
+                    (
+                        quote! { #enum_type::#variant_ident { #(#names),* } },
+                        quote! { #struct_ident { #(#names),* } },
+                    )
+                }
+                None if v.is_unit => {
+                    (quote! { #enum_type::#variant_ident }, quote! { () })
+                }
+                None => (
+                    quote! { #enum_type::#variant_ident(payload) },
+                    quote! { payload },
+                ),
+            };
+            quote! {
+                #item_attrs #pattern => {
+                    self.#field.push(#payload);
+                    #index
+                }
+            }
+        },
+    );
 
-                let generate_macro_rule_arm = generate_macro_rule_arm(
-                    enum_name,
-                    &group_variants,
-                    tokens_path.clone(),
-                    &dtype_variant_path,
-                    Some(group_index as _),
-                );
+    // Cloning iteration needs every payload to be `Clone`; the bounds are
+    // higher-ranked so that other payloads only disable `iter_ordered`.
+    let clone_bounds = parsed_variants
+        .iter()
+        .filter_map(|v| v.full_field_type.as_ref())
+        .map(|ty| quote! { for<'__clone> #ty: Clone });
 
-                let all_unit_variants =
-                    group_variants.iter().all(|info| info.is_unit);
+    let bucket_impls = parsed_variants
+        .iter()
+        .zip(&fields)
+        .zip(&payloads)
+        .map(|((v, field), payload)| {
+            let token_ident = &v.token_ident;
+            v.scoped_items(quote! {
+                impl #impl_generics #dtype_variant_path::VariantBucket<#tokens_path::#token_ident>
+                    for #buckets_ident #ty_generics #where_clause
+                {
+                    type Payload = #payload;
 
-                // #(#group_variants,)*
-                let arm = if all_unit_variants {
-                    generate_macro_rule_arm(
-                        false, false, false, false, false, false,
-                    )
-                } else {
-                    generate_macro_rule_arm(
-                        include_src_ty,
-                        true,
-                        false,
-                        false,
-                        false,
-                        false,
-                    )
-                };
+                    fn bucket(&self) -> &[Self::Payload] {
+                        &self.#field
+                    }
 
-                (group_name, arm)
+                    fn bucket_mut(&mut self) -> &mut [Self::Payload] {
+                        &mut self.#field
+                    }
+                }
             })
-            .collect::<Vec<_>>();
-
-        let group_match_pattern_fragment = {
-            let fragments = group_pattern_arms.iter().map(|(name, arm)| {
-                let prefix = &arm.pattern_prefix_fragment;
-                let suffix = &arm.pattern_suffix_fragment;
-                quote! {
-                    #name : #prefix #suffix
+        });
+
+    let enum_ty = quote! { #enum_type #ty_generics };
+
+    quote! {
+        #[doc = concat!("The values of [`", stringify!(#enum_type), "`] stored with one `Vec` per variant.")]
+        ///
+        /// Pushed values are routed to the bucket of their variant, and the
+        /// variant of every value is recorded to iterate them back in insertion
+        /// order, unless created with `unordered`.
+        #vis struct #buckets_ident #impl_generics #where_clause {
+            #(#attrs #fields: Vec<#payloads>,)*
+            __order: Option<Vec<u32>>,
+        }
+
+        #allow_deprecated
+        impl #impl_generics #buckets_ident #ty_generics #where_clause {
+            /// Creates empty buckets that record the insertion order.
+            pub fn new() -> Self {
+                Self {
+                    #(#attrs #fields: Vec::new(),)*
+                    __order: Some(Vec::new()),
                 }
-            });
+            }
 
-            quote! {
-                {
-                    #(#fragments,)*
+            /// Creates empty buckets without the order index, which iterate
+            /// variant by variant in declaration order.
+            pub fn unordered() -> Self {
+                Self {
+                    #(#attrs #fields: Vec::new(),)*
+                    __order: None,
+                }
+            }
+
+            /// Returns `true` if the insertion order is recorded.
+            pub fn is_ordered(&self) -> bool {
+                self.__order.is_some()
+            }
+
+            /// Moves the payload of `value` to the bucket of its variant.
+            pub fn push(&mut self, value: #enum_ty) {
+                let index: u32 = match value {
+                    #(#push_arms)*
+                };
+                if let Some(order) = &mut self.__order {
+                    order.push(index);
+                }
+            }
+
+            /// Returns the payloads of the variant of `Token`, in insertion order.
+            pub fn get<Token>(&self) -> &[<Self as #dtype_variant_path::VariantBucket<Token>>::Payload]
+            where
+                Self: #dtype_variant_path::VariantBucket<Token>,
+            {
+                <Self as #dtype_variant_path::VariantBucket<Token>>::bucket(self)
+            }
+
+            /// Returns the payloads of the variant of `Token` mutably.
+            pub fn get_mut<Token>(&mut self) -> &mut [<Self as #dtype_variant_path::VariantBucket<Token>>::Payload]
+            where
+                Self: #dtype_variant_path::VariantBucket<Token>,
+            {
+                <Self as #dtype_variant_path::VariantBucket<Token>>::bucket_mut(self)
+            }
+
+            /// Returns the number of values of the variant of `Token`.
+            pub fn len_of<Token>(&self) -> usize
+            where
+                Self: #dtype_variant_path::VariantBucket<Token>,
+            {
+                self.get::<Token>().len()
+            }
+
+            /// Returns the number of values of each variant, indexed by
+            /// `variant_index`.
+            pub fn variant_lens(&self) -> [usize; #variant_count] {
+                [#(self.#fields.len()),*]
+            }
+
+            /// Returns the number of values of all variants.
+            pub fn len(&self) -> usize {
+                0 #(+ self.#fields.len())*
+            }
+
+            /// Returns `true` if no value was pushed.
+            pub fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            /// Rebuilds the values in insertion order, or variant by variant
+            /// for unordered buckets.
+            pub fn into_ordered(self) -> impl Iterator<Item = #enum_ty> {
+                let Self { #(#fields,)* __order } = self;
+                #(#attrs let mut #fields = #fields.into_iter();)*
+                let mut order = __order.map(Vec::into_iter);
+                ::core::iter::from_fn(move || match &mut order {
+                    Some(order) => match order.next()? {
+                        #(#attrs #indices => #fields.next().map(#constructs),)*
+                        _ => None,
+                    },
+                    None => {
+                        #(#attrs if let Some(payload) = #fields.next() {
+                            return Some(#constructs(payload));
+                        })*
+                        None
+                    }
+                })
+            }
+
+            /// Clones the values in insertion order, or variant by variant
+            /// for unordered buckets.
+            pub fn iter_ordered(&self) -> impl Iterator<Item = #enum_ty> + '_
+            where
+                #(#clone_bounds,)*
+            {
+                let Self { #(#fields,)* __order } = self;
+                #(#attrs let mut #fields = #fields.iter().cloned();)*
+                let mut order = __order.as_ref().map(|order| order.iter());
+                ::core::iter::from_fn(move || match &mut order {
+                    Some(order) => match *order.next()? {
+                        #(#attrs #indices => #fields.next().map(#constructs),)*
+                        _ => None,
+                    },
+                    None => {
+                        #(#attrs if let Some(payload) = #fields.next() {
+                            return Some(#constructs(payload));
+                        })*
+                        None
+                    }
+                })
+            }
+        }
+
+        impl #impl_generics Default for #buckets_ident #ty_generics #where_clause {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl #impl_generics Extend<#enum_ty> for #buckets_ident #ty_generics #where_clause {
+            fn extend<I: IntoIterator<Item = #enum_ty>>(&mut self, values: I) {
+                for value in values {
+                    self.push(value);
                 }
             }
+        }
+
+        impl #impl_generics FromIterator<#enum_ty> for #buckets_ident #ty_generics #where_clause {
+            fn from_iter<I: IntoIterator<Item = #enum_ty>>(values: I) -> Self {
+                let mut buckets = Self::new();
+                buckets.extend(values);
+                buckets
+            }
+        }
+
+        impl #impl_generics From<Vec<#enum_ty>> for #buckets_ident #ty_generics #where_clause {
+            fn from(values: Vec<#enum_ty>) -> Self {
+                values.into_iter().collect()
+            }
+        }
+
+        impl #impl_generics From<#buckets_ident #ty_generics> for Vec<#enum_ty> #where_clause {
+            fn from(buckets: #buckets_ident #ty_generics) -> Self {
+                buckets.into_ordered().collect()
+            }
+        }
+
+        #(#bucket_impls)*
+    }
+}
+
+/// Generates `variant_index` and `default_for_index`, which identify variants
+/// by their position in declaration order, and the `VariantIndex`,
+/// `VariantWords` and `VariantTokenIndex` impls.
+fn generate_variant_index_methods(
+    dtype_variant_path: &Path,
+    enum_name: &Ident,
+    generics: &Generics,
+    parsed_variants: &[ParsedVariantInfo],
+    tokens_path: &Path,
+) -> TokenStream2 {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let index_arms = parsed_variants.iter().enumerate().map(|(index, v)| {
+        let variant_ident = &v.variant_ident;
+        let pattern = if v.is_unit {
+            quote! { Self::#variant_ident }
+        } else if v.is_struct {
+            quote! { Self::#variant_ident { .. } }
+        } else {
+            quote! { Self::#variant_ident(_) }
         };
+        let item_attrs = v.attrs.item_attrs();
+        quote! { #item_attrs #pattern => #index }
+    });
 
-        let all_bodies = {
-            let ts = group_pattern_arms
-                .iter()
-                .map(|(_, arm)| &arm.variant_bodies);
+    let default_arms = parsed_variants.iter().enumerate().map(|(index, v)| {
+        let variant_ident = &v.variant_ident;
+        let construction = match &v.struct_fields {
+            Some(fields) => {
+                let field_names = fields.iter().filter_map(|f| f.ident.as_ref());
+                quote! { Self::#variant_ident { #(#field_names: Default::default()),* } }
+            }
+            None if v.is_unit => quote! { Self::#variant_ident },
+            None => quote! { Self::#variant_ident(Default::default()) },
+        };
+        let item_attrs = v.attrs.item_attrs();
+        quote! { #item_attrs #index => Some(#construction) }
+    });
 
-            quote! {
-                #(#ts,)*
+    let variant_count = parsed_variants.len();
+    let variant_words = variant_count.div_ceil(64).max(1);
+
+    let token_index_impls = parsed_variants.iter().enumerate().map(|(index, v)| {
+        let token_ident = &v.token_ident;
+        v.scoped_items(quote! {
+            impl #impl_generics #dtype_variant_path::VariantTokenIndex<#tokens_path::#token_ident>
+                for #enum_name #ty_generics #where_clause
+            {
+                const INDEX: usize = #index;
             }
+        })
+    });
+
+    // Every payload must be `Default`. The bounds are higher-ranked so that a
+    // payload without a default makes the method unavailable instead of
+    // failing the derive.
+    let default_bounds = parsed_variants.iter().flat_map(|v| {
+        let field_types: Vec<&Type> = match &v.struct_fields {
+            Some(fields) => fields.iter().map(|f| &f.ty).collect(),
+            None => v.full_field_type.iter().collect(),
         };
+        field_types
+            .into_iter()
+            .map(|ty| quote! { for<'__default> #ty: Default })
+    });
 
-        quote! {
-            // Match the user's grouped input structure
-            ( $value:expr, #group_match_pattern_fragment ) => {
-                // Expand into the actual Rust match statement
-                match $value {
-                    #all_bodies // Expand the generated match arms here
+    quote! {
+        impl #impl_generics #enum_name #ty_generics #where_clause {
+            /// Returns the position of the variant in declaration order.
+            pub const fn variant_index(&self) -> usize {
+                match self {
+                    #(#index_arms,)*
                 }
-            };
+            }
+
+            /// Creates the variant at `index` in declaration order with default payloads,
+            /// or `None` if the index is out of range.
+            pub fn default_for_index(index: usize) -> Option<Self>
+            where
+                #(#default_bounds,)*
+            {
+                match index {
+                    #(#default_arms,)*
+                    _ => None,
+                }
+            }
         }
-    };
 
-    let pat1 = create_group_macro_arm(true);
-    let pat2 = create_group_macro_arm(false);
+        impl #impl_generics #dtype_variant_path::VariantIndex for #enum_name #ty_generics #where_clause {
+            const VARIANT_COUNT: usize = #variant_count;
 
-    quote! {
-        #[doc(hidden)]
-        #[macro_export]
-        macro_rules! #internal_macro_name {
-            // Match the user's grouped input structure
-            #pat1
-            #pat2
+            fn variant_index(&self) -> usize {
+                Self::variant_index(self)
+            }
+        }
+
+        impl #impl_generics #dtype_variant_path::VariantWords<#variant_words>
+            for #enum_name #ty_generics #where_clause
+        {
+        }
+
+        #(#token_index_impls)*
+    }
+}
+
+/// Where the matcher macros of an enum are defined and how they are named.
+struct MatcherScope {
+    vis: Visibility,
+    /// The remote enum matched on, for mirrors of remote enums.
+    remote: Option<TokenStream2>,
+    /// Hash of the derive input and its site, which keeps the names of the
+    /// underlying `macro_rules!` unique among all matchers of a crate.
+    input_hash: u64,
+}
+
+impl MatcherScope {
+    fn new(derive_input: &DeriveInput, args: &DTypeMacroArgs) -> Self {
+        Self {
+            vis: args
+                .matcher_vis
+                .clone()
+                .unwrap_or_else(|| parse_quote!(pub)),
+            remote: args.remote.as_ref().map(macro_compatible_path),
+            input_hash: site_hash(derive_input, derive_input.ident.span()),
+        }
+    }
+
+    /// Defines the matcher macro `name`, forwarding to `dtype_match!`.
+    ///
+    /// Public matchers are `#[macro_export]`ed, which places them at the crate
+    /// root; they get a mangled name there so matchers of different enums
+    /// cannot collide, and are re-exported under their own name next to the
+    /// enum. Other matchers are plain `macro_rules!` re-exported with `vis`.
+    fn define(
+        &self,
+        name: &Ident,
+        metadata: TokenStream2,
+        dtype_variant_path: &TokenStream2,
+    ) -> TokenStream2 {
+        let internal_name =
+            format_ident!("__dtype_{}_{:016x}", name, self.input_hash);
+        let vis = &self.vis;
+        let export = matches!(vis, Visibility::Public(_))
+            .then(|| quote! { #[macro_export] });
+        quote! {
+            #[doc(hidden)]
+            #export
+            macro_rules! #internal_name {
+                ($($input:tt)*) => {
+                    #dtype_variant_path::dtype_match! { #metadata $($input)* }
+                };
+            }
+            #[allow(unused_imports)]
+            #vis use #internal_name as #name;
         }
-        #[allow(unused_imports)]
-        pub use #internal_macro_name as #macro_name;
     }
 }
+
+/// Generates `impl #dtype_variant_path::VariantTokenAt<INDEX>` blocks for
+/// every variant, through which matchers name the tokens wherever the enum is
+/// in scope. Remote enums get them on their local mirror.
+fn generate_variant_token_at_impls(
+    dtype_variant_path: &Path,
+    enum_name: &Ident,
+    generics: &Generics,
+    parsed_variants: &[ParsedVariantInfo],
+    tokens_path: &Path,
+) -> TokenStream2 {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let token_at_impls =
+        parsed_variants.iter().enumerate().map(|(index, v)| {
+            let token_ident = &v.token_ident;
+            v.scoped_items(quote! {
+                impl #impl_generics #dtype_variant_path::VariantTokenAt<#index>
+                    for #enum_name #ty_generics #where_clause
+                {
+                    type Token = #tokens_path::#token_ident;
+                }
+            })
+        });
+
+    quote! { #(#token_at_impls)* }
+}
+
+/// Generates a macro for pattern matching on enum variants if `matcher` name is provided.
+/// The macro forwards its input, along with the enum metadata, to `dtype_match!`.
+fn generate_matcher_method(
+    dtype_variant_path: &Path,
+    enum_name: &Ident,
+    generics: &Generics,
+    parsed_variants: &[ParsedVariantInfo],
+    matcher_ident: &Option<Ident>,
+    tokens_path: &Path,
+    scope: &MatcherScope,
+) -> TokenStream2 {
+    let matcher_name = match matcher_ident {
+        Some(ident) => ident,
+        None => return quote! {}, // No matcher name specified
+    };
+
+    // Generate macro-compatible paths
+    let (tokens_path, dtype_variant_path) =
+        generate_macro_compatible_paths(tokens_path, dtype_variant_path);
+
+    let metadata = generate_matcher_metadata(
+        enum_name,
+        generics,
+        parsed_variants,
+        &[],
+        &tokens_path,
+        &dtype_variant_path,
+        scope.remote.as_ref(),
+    );
+
+    scope.define(matcher_name, metadata, &dtype_variant_path)
+}
+
+/// Generates the grouped matcher macro if `grouped_matcher` is specified.
+/// Like the regular matcher, it forwards to `dtype_match!`, passing the groups
+/// along with the enum metadata.
+fn generate_grouped_matcher_macro(
+    dtype_variant_path: &Path,
+    enum_name: &Ident,
+    generics: &Generics,
+    parsed_variants: &[ParsedVariantInfo],
+    parsed_grouped_matcher: &ParsedGroupedMatcher,
+    tokens_path: &Path,
+    scope: &MatcherScope,
+) -> TokenStream2 {
+    let macro_name = &parsed_grouped_matcher.macro_name;
+
+    // Generate macro-compatible paths
+    let (tokens_path, dtype_variant_path) =
+        generate_macro_compatible_paths(tokens_path, dtype_variant_path);
+
+    let metadata = generate_matcher_metadata(
+        enum_name,
+        generics,
+        parsed_variants,
+        &parsed_grouped_matcher.groups,
+        &tokens_path,
+        &dtype_variant_path,
+        scope.remote.as_ref(),
+    );
+
+    scope.define(macro_name, metadata, &dtype_variant_path)
+}
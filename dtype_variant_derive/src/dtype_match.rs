@@ -0,0 +1,1583 @@
+//! The `dtype_match!` engine behind the generated matcher macros.
+//!
+//! The derive emits a thin `macro_rules!` matcher which forwards the user's
+//! input to `dtype_match!`, prefixed with `@meta { .. }` describing the enum
+//! (variants, tokens and payload types). Parsing the user's input here
+//! instead of in `macro_rules!` keeps the grammar flexible and lets errors
+//! point at the user's code.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Delimiter, Group, Span, TokenStream as TokenStream2};
+use quote::{ToTokens, format_ident, quote};
+use syn::parse::{Parse, ParseStream, Parser};
+use syn::punctuated::Punctuated;
+use syn::{
+    AngleBracketedGenericArguments, Attribute, Error, Expr, GenericParam,
+    Generics, Ident, Lifetime, LifetimeParam, LitBool, LitInt, Pat, Path,
+    Token, WhereClause, braced, bracketed, parenthesized, parse_macro_input,
+};
+
+use crate::derive::ParsedVariantInfo;
+
+//============================================================================
+// Enum Metadata
+//============================================================================
+
+/// Everything the engine needs to know about the matched enum.
+struct MatcherMetadata {
+    enum_name: Ident,
+    /// The remote enum matched on, when `enum_name` mirrors one.
+    remote: Option<Path>,
+    tokens_path: TokenStream2,
+    dtype_variant_path: TokenStream2,
+    variants: Vec<VariantMetadata>,
+    /// Variant groups of a grouped matcher; empty for regular matchers.
+    groups: Vec<(Ident, Vec<Ident>)>,
+    /// Number of lifetime parameters of the enum.
+    lifetimes: usize,
+    /// Whether the enum has type or const parameters.
+    type_params: bool,
+}
+
+impl MatcherMetadata {
+    /// Whether the enum has generic parameters of any kind.
+    fn is_generic(&self) -> bool {
+        self.lifetimes > 0 || self.type_params
+    }
+}
+
+/// Shape of a variant, as far as binding its payload is concerned.
+enum VariantKind {
+    Unit,
+    Tuple,
+    Struct {
+        fields: Vec<Ident>,
+        /// Names of the generated `Fields`, `Ref` and `Mut` structs, used to
+        /// construct them without spelling out their generic arguments.
+        fields_ident: Ident,
+        ref_ident: Ident,
+        mut_ident: Ident,
+    },
+}
+
+/// A single variant of the matched enum.
+struct VariantMetadata {
+    /// Attributes put on the match arms of the variant, such as an `allow`
+    /// for deprecated variants.
+    attrs: Vec<Attribute>,
+    /// Position of the variant in declaration order.
+    index: usize,
+    variant_ident: Ident,
+    token_ident: Ident,
+    kind: VariantKind,
+}
+
+/// Generates the `@meta { .. }` block the derive passes to `dtype_match!`.
+///
+/// `tokens_path`, `dtype_variant_path` and `remote` must already be macro
+/// compatible, since the block is emitted inside the generated
+/// `macro_rules!` matcher.
+pub(crate) fn generate_matcher_metadata(
+    enum_name: &Ident,
+    generics: &Generics,
+    parsed_variants: &[ParsedVariantInfo],
+    groups: &[(Ident, Vec<Ident>)],
+    tokens_path: &TokenStream2,
+    dtype_variant_path: &TokenStream2,
+    remote: Option<&TokenStream2>,
+) -> TokenStream2 {
+    let variants = parsed_variants.iter().map(|v| {
+        let variant_ident = &v.variant_ident;
+        let token_ident = &v.token_ident;
+        // The variant's `cfg` is left out: the derive only sees enabled
+        // variants, and it would be evaluated where the matcher is invoked.
+        let attrs = v.attrs.deprecated.then(|| quote! { #[allow(deprecated)] });
+        let entry = if v.is_unit {
+            quote! { unit #variant_ident = #token_ident }
+        } else if v.is_struct {
+            let fields = v
+                .struct_fields
+                .iter()
+                .flatten()
+                .filter_map(|field| field.ident.as_ref());
+            let fields_ident =
+                format_ident!("{}{}Fields", enum_name, variant_ident);
+            let ref_ident = format_ident!("{}{}Ref", enum_name, variant_ident);
+            let mut_ident = format_ident!("{}{}Mut", enum_name, variant_ident);
+            quote! {
+                struct #variant_ident = #token_ident [#(#fields),*]
+                    #fields_ident #ref_ident #mut_ident
+            }
+        } else {
+            quote! { tuple #variant_ident = #token_ident }
+        };
+        quote! { #attrs #entry }
+    });
+
+    let groups = groups.iter().map(|(group_name, group_variants)| {
+        quote! { #group_name(#(#group_variants),*) }
+    });
+
+    let lifetimes = generics.lifetimes().count();
+    let type_params = generics.params.len() > lifetimes;
+
+    quote! {
+        @meta {
+            enum_name = #enum_name,
+            remote = { #remote },
+            tokens_path = { #tokens_path },
+            dtype_variant_path = { #dtype_variant_path },
+            variants = [#(#variants),*],
+            groups = [#(#groups),*],
+            lifetimes = #lifetimes,
+            type_params = #type_params,
+        }
+    }
+}
+
+/// Parses `key = ` and checks the key name.
+fn parse_key(input: ParseStream, key: &str) -> syn::Result<()> {
+    let ident: Ident = input.parse()?;
+    if ident != key {
+        return Err(Error::new(
+            ident.span(),
+            format!("expected `{}` in matcher metadata", key),
+        ));
+    }
+    input.parse::<Token![=]>()?;
+    Ok(())
+}
+
+/// Parses the content of a `{ .. }` group as raw tokens.
+fn parse_braced_tokens(input: ParseStream) -> syn::Result<TokenStream2> {
+    let content;
+    braced!(content in input);
+    content.parse()
+}
+
+impl Parse for VariantMetadata {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let is_struct = input.peek(Token![struct]);
+        let kind_ident = if is_struct {
+            input.parse::<Token![struct]>()?;
+            None
+        } else {
+            Some(input.parse::<Ident>()?)
+        };
+        let variant_ident: Ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let token_ident: Ident = input.parse()?;
+
+        match kind_ident {
+            None => {
+                let content;
+                bracketed!(content in input);
+                let fields = content
+                    .parse_terminated(Ident::parse, Token![,])?
+                    .into_iter()
+                    .collect();
+                let fields_ident: Ident = input.parse()?;
+                let ref_ident: Ident = input.parse()?;
+                let mut_ident: Ident = input.parse()?;
+                Ok(VariantMetadata {
+                    attrs,
+                    index: 0,
+                    variant_ident,
+                    token_ident,
+                    kind: VariantKind::Struct {
+                        fields,
+                        fields_ident,
+                        ref_ident,
+                        mut_ident,
+                    },
+                })
+            }
+            Some(kind) if kind == "tuple" => Ok(VariantMetadata {
+                attrs,
+                index: 0,
+                variant_ident,
+                token_ident,
+                kind: VariantKind::Tuple,
+            }),
+            Some(kind) if kind == "unit" => Ok(VariantMetadata {
+                attrs,
+                index: 0,
+                variant_ident,
+                token_ident,
+                kind: VariantKind::Unit,
+            }),
+            Some(kind) => Err(Error::new(
+                kind.span(),
+                "expected `unit`, `tuple` or `struct` in matcher metadata",
+            )),
+        }
+    }
+}
+
+impl Parse for MatcherMetadata {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        input.parse::<Token![@]>()?;
+        let meta: Ident = input.parse()?;
+        if meta != "meta" {
+            return Err(Error::new(
+                meta.span(),
+                "`dtype_match!` is invoked by the matcher macros generated by `#[derive(DType)]`",
+            ));
+        }
+
+        let content;
+        braced!(content in input);
+        parse_key(&content, "enum_name")?;
+        let enum_name: Ident = content.parse()?;
+        content.parse::<Token![,]>()?;
+        parse_key(&content, "remote")?;
+        let remote_content;
+        braced!(remote_content in content);
+        let remote = match remote_content.is_empty() {
+            true => None,
+            false => Some(Path::parse_mod_style(&remote_content)?),
+        };
+        content.parse::<Token![,]>()?;
+        parse_key(&content, "tokens_path")?;
+        let tokens_path = parse_braced_tokens(&content)?;
+        content.parse::<Token![,]>()?;
+        parse_key(&content, "dtype_variant_path")?;
+        let dtype_variant_path = parse_braced_tokens(&content)?;
+        content.parse::<Token![,]>()?;
+        parse_key(&content, "variants")?;
+        let variants_content;
+        bracketed!(variants_content in content);
+        let variants = variants_content
+            .parse_terminated(VariantMetadata::parse, Token![,])?
+            .into_iter()
+            .enumerate()
+            .map(|(index, variant)| VariantMetadata { index, ..variant })
+            .collect();
+        content.parse::<Token![,]>()?;
+        parse_key(&content, "groups")?;
+        let groups_content;
+        bracketed!(groups_content in content);
+        let groups = groups_content
+            .parse_terminated(
+                |input| {
+                    let group_name: Ident = input.parse()?;
+                    let variants;
+                    parenthesized!(variants in input);
+                    let variants =
+                        variants.parse_terminated(Ident::parse, Token![,])?;
+                    Ok((group_name, variants.into_iter().collect()))
+                },
+                Token![,],
+            )?
+            .into_iter()
+            .collect();
+        content.parse::<Token![,]>()?;
+        parse_key(&content, "lifetimes")?;
+        let lifetimes = content.parse::<LitInt>()?.base10_parse()?;
+        content.parse::<Token![,]>()?;
+        parse_key(&content, "type_params")?;
+        let type_params = content.parse::<LitBool>()?.value;
+        content.parse::<Option<Token![,]>>()?;
+
+        Ok(MatcherMetadata {
+            enum_name,
+            remote,
+            tokens_path,
+            dtype_variant_path,
+            variants,
+            groups,
+            lifetimes,
+            type_params,
+        })
+    }
+}
+
+//============================================================================
+// Matcher Grammar
+//============================================================================
+
+/// A type alias declared by the user, e.g. `T` or `Dest<'a>`.
+///
+/// Generic parameters are declared on the alias and also passed as generic
+/// arguments to the enum the alias is resolved against.
+struct AliasSpec {
+    ident: Ident,
+    generics: Generics,
+}
+
+impl Parse for AliasSpec {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident = input.parse()?;
+        let generics = if input.peek(Token![<]) {
+            input.parse()?
+        } else {
+            Generics::default()
+        };
+        Ok(AliasSpec { ident, generics })
+    }
+}
+
+/// An enum followed by the aliases to declare for it, e.g. `DynChunk<T, V>`.
+///
+/// The enum may be path-qualified and may carry explicit generic arguments
+/// ahead of the alias list, e.g. `crate::chunks::Borrowed<'a><Dest>`.
+struct EnumSpec {
+    path: Path,
+    generic_args: Option<AngleBracketedGenericArguments>,
+    aliases: Vec<AliasSpec>,
+    /// The remote enum actually matched on, when `path` names a mirror.
+    /// Tokens and generated structs are still found next to the mirror.
+    remote: Option<Path>,
+}
+
+impl EnumSpec {
+    /// The enum name, i.e. the last segment of the path.
+    fn enum_ident(&self) -> &Ident {
+        &self.path.segments.last().unwrap().ident
+    }
+
+    /// The enum path as written in expression and pattern position.
+    fn expr_path(&self) -> TokenStream2 {
+        let path = self.remote.as_ref().unwrap_or(&self.path);
+        match &self.generic_args {
+            Some(args) => {
+                let args = &args.args;
+                quote! { #path::<#args> }
+            }
+            None => quote! { #path },
+        }
+    }
+
+    /// The path of the module the enum is defined in, as written, or `None`
+    /// for a bare enum name.
+    fn module_path(&self) -> Option<TokenStream2> {
+        let segments = &self.path.segments;
+        if segments.len() < 2 {
+            return None;
+        }
+        let leading_colon = &self.path.leading_colon;
+        let module = segments.iter().take(segments.len() - 1).map(|s| &s.ident);
+        Some(quote! { #leading_colon #(#module)::* })
+    }
+
+    /// Joins `item` onto the module path of the enum.
+    fn sibling_path(&self, item: &Ident) -> TokenStream2 {
+        match self.module_path() {
+            Some(module) => quote! { #module::#item },
+            None => quote! { #item },
+        }
+    }
+
+    /// The enum type that tokens and generated structs are reached through,
+    /// or `None` for a generic enum. Its generic arguments may borrow from or
+    /// name parameters of the caller, which the declarations of an arm
+    /// cannot, so those are found by path instead.
+    ///
+    /// Tokens are named through the enum's own impls (on the mirror of a
+    /// remote enum), which resolve wherever the enum's name is in scope.
+    fn projected_type(
+        &self,
+        metadata: &MatcherMetadata,
+        remote: bool,
+    ) -> Option<TokenStream2> {
+        let path = match remote {
+            true => self.remote.as_ref().unwrap_or(&self.path),
+            false => &self.path,
+        };
+        (!metadata.is_generic()).then(|| quote! { #path })
+    }
+
+    /// Resolves the token of `variant` through the enum's `VariantTokenAt`
+    /// impl, or else by path, see `rebased_token_path`.
+    fn token_path(
+        &self,
+        metadata: &MatcherMetadata,
+        variant: &VariantMetadata,
+    ) -> TokenStream2 {
+        let dtype_variant_path = &metadata.dtype_variant_path;
+        match self.projected_type(metadata, false) {
+            Some(enum_type) => {
+                let index = variant.index;
+                quote! {
+                    <#enum_type as #dtype_variant_path::VariantTokenAt<#index>>::Token
+                }
+            }
+            None => self.rebased_token_path(
+                &metadata.tokens_path,
+                &variant.token_ident,
+            ),
+        }
+    }
+
+    /// Resolves the path to a token. Token paths relative to the enum's
+    /// module (`self`, `super`) are rebased onto the enum path as written, so
+    /// that the matcher finds the tokens from any module or crate that can
+    /// name the enum. Other paths are used as is.
+    fn rebased_token_path(
+        &self,
+        tokens_path: &TokenStream2,
+        token_ident: &Ident,
+    ) -> TokenStream2 {
+        let Ok(tokens_path) = Path::parse_mod_style.parse2(tokens_path.clone())
+        else {
+            return quote! { #tokens_path::#token_ident };
+        };
+        let is_keyword = |ident: &Ident| {
+            ["self", "super", "crate", "$crate"].contains(&&*ident.to_string())
+        };
+
+        let mut module: Vec<Ident> = self
+            .path
+            .segments
+            .iter()
+            .take(self.path.segments.len() - 1)
+            .map(|segment| segment.ident.clone())
+            .collect();
+        let mut rest = tokens_path.segments.iter().map(|s| &s.ident).peekable();
+        match rest.peek() {
+            Some(first) if *first == "self" => {
+                rest.next();
+            }
+            Some(first) if *first == "super" => {
+                while let Some(ident) = rest.next_if(|ident| *ident == "super")
+                {
+                    match module.last() {
+                        Some(last) if !is_keyword(last) => {
+                            module.pop();
+                        }
+                        _ => module.push(ident.clone()),
+                    }
+                }
+            }
+            _ => return quote! { #tokens_path::#token_ident },
+        }
+        module.extend(rest.cloned());
+
+        let leading_colon =
+            module.first().and(self.path.leading_colon.as_ref());
+        quote! { #leading_colon #(#module::)* #token_ident }
+    }
+
+    /// The generic parameters to declare an alias with, and the enum type it
+    /// resolves against. Without explicit generic arguments, the alias' own
+    /// generic parameters are passed to the enum, led by one for each of the
+    /// enum's `lifetimes` if the alias declares none.
+    fn type_for_alias(
+        &self,
+        alias: &AliasSpec,
+        lifetimes: usize,
+    ) -> (Generics, TokenStream2) {
+        let path = self.remote.as_ref().unwrap_or(&self.path);
+        let mut generics = alias.generics.clone();
+        match &self.generic_args {
+            Some(args) => (generics, quote! { #path #args }),
+            None => {
+                if generics.lifetimes().next().is_none() {
+                    let params = (0..lifetimes).map(|index| {
+                        let lifetime = Lifetime::new(
+                            &format!("'__dtype{}", index),
+                            Span::mixed_site(),
+                        );
+                        GenericParam::Lifetime(LifetimeParam::new(lifetime))
+                    });
+                    generics.params = params.chain(generics.params).collect();
+                }
+                let (_, ty_generics, _) = generics.split_for_impl();
+                let enum_type = quote! { #path #ty_generics };
+                (generics, enum_type)
+            }
+        }
+    }
+
+    /// Error for an alias list of the wrong length.
+    fn usage_error(&self, expected: &[&str]) -> Error {
+        let usage = expected
+            .iter()
+            .map(|aliases| format!("`{}<{}>`", self.enum_ident(), aliases))
+            .collect::<Vec<_>>()
+            .join(" or ");
+        Error::new_spanned(&self.path, format!("expected {}", usage))
+    }
+}
+
+impl Parse for EnumSpec {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path = Path::parse_mod_style(input)?;
+
+        // Two angle-bracketed lists in a row: the first holds generic args.
+        let has_generic_args = {
+            let fork = input.fork();
+            fork.parse::<AngleBracketedGenericArguments>().is_ok()
+                && fork.peek(Token![<])
+        };
+        let generic_args = match has_generic_args {
+            true => Some(input.parse()?),
+            false => None,
+        };
+
+        input.parse::<Token![<]>()?;
+        let mut aliases = Vec::new();
+        while !input.peek(Token![>]) {
+            aliases.push(input.parse()?);
+            if !input.peek(Token![>]) {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        input.parse::<Token![>]>()?;
+        Ok(EnumSpec {
+            path,
+            generic_args,
+            aliases,
+            remote: None,
+        })
+    }
+}
+
+/// The source enum spec: `Enum<Token>` or `Enum<Src, Token>`.
+struct SourceSpec {
+    spec: EnumSpec,
+    src_alias: Option<AliasSpec>,
+    token_alias: Ident,
+}
+
+impl SourceSpec {
+    fn from_enum_spec(mut spec: EnumSpec) -> syn::Result<Self> {
+        let mut aliases = std::mem::take(&mut spec.aliases).into_iter();
+        let (src_alias, token_alias) =
+            match (aliases.next(), aliases.next(), aliases.next()) {
+                (Some(token), None, None) => (None, token),
+                (Some(src), Some(token), None) => (Some(src), token),
+                _ => return Err(spec.usage_error(&["Token", "Src, Token"])),
+            };
+        if !token_alias.generics.params.is_empty() {
+            return Err(Error::new_spanned(
+                &token_alias.generics,
+                "the token alias cannot take generic parameters",
+            ));
+        }
+        Ok(SourceSpec {
+            spec,
+            src_alias,
+            token_alias: token_alias.ident,
+        })
+    }
+}
+
+/// A destination enum spec: `Enum<Dest>` or `Enum<Dest, Constraint>`.
+struct DestSpec {
+    spec: EnumSpec,
+    dest_alias: AliasSpec,
+    constraint_alias: Option<AliasSpec>,
+}
+
+impl DestSpec {
+    fn from_enum_spec(mut spec: EnumSpec) -> syn::Result<Self> {
+        let mut aliases = std::mem::take(&mut spec.aliases).into_iter();
+        match (aliases.next(), aliases.next(), aliases.next()) {
+            (Some(dest_alias), constraint_alias, None) => Ok(DestSpec {
+                spec,
+                dest_alias,
+                constraint_alias,
+            }),
+            _ => Err(spec.usage_error(&["Dest", "Dest, Constraint"])),
+        }
+    }
+}
+
+/// `Enum<Src, Token>(inner), Dest<D, C>, .. where .. => body`
+struct MatchArm {
+    source: SourceSpec,
+    binding: Option<Pat>,
+    dests: Vec<DestSpec>,
+    where_clause: Option<WhereClause>,
+    body: Expr,
+}
+
+impl Parse for MatchArm {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let source = SourceSpec::from_enum_spec(input.parse()?)?;
+        let binding = if input.peek(syn::token::Paren) {
+            let content;
+            parenthesized!(content in input);
+            let binding = Pat::parse_single(&content)?;
+            if !content.is_empty() {
+                return Err(content.error("expected a single binding"));
+            }
+            Some(binding)
+        } else {
+            None
+        };
+        let mut dests = Vec::new();
+        while input.peek(Token![,]) {
+            input.parse::<Token![,]>()?;
+            dests.push(DestSpec::from_enum_spec(input.parse()?)?);
+        }
+        let where_clause = input.parse()?;
+        input.parse::<Token![=>]>()?;
+        let body = input.parse()?;
+        Ok(MatchArm {
+            source,
+            binding,
+            dests,
+            where_clause,
+            body,
+        })
+    }
+}
+
+/// How the matched value is bound inside the generated match arms.
+///
+/// Selected by an optional prefix on the matched value, e.g.
+/// `match_enum!(ref chunk, ...)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BindingMode {
+    /// No prefix: `match value { .. }` with default binding modes.
+    Default,
+    /// `ref value`: matches on `&value`, binding shared references.
+    Ref,
+    /// `mut value`: matches on `&mut value`, binding mutable references.
+    Mut,
+    /// `move value`: matches on `value`, binding owned payloads.
+    Move,
+    /// `all values`: matches on the first element of a slice, binding the
+    /// shared references of every element, which must hold the same variant.
+    All,
+}
+
+impl BindingMode {
+    /// Wraps the matched value into the expression that is matched on.
+    fn scrutinee(self, value: &TokenStream2) -> TokenStream2 {
+        match self {
+            BindingMode::Default | BindingMode::Move => quote! { #value },
+            BindingMode::Ref => quote! { &#value },
+            BindingMode::Mut => quote! { &mut #value },
+            BindingMode::All => {
+                let first = batch_ident("first");
+                quote! { #first }
+            }
+        }
+    }
+
+    /// Suffix of the alias naming the type actually bound to the inner
+    /// value (e.g. `SrcRef<'_>` for `Src`). `None` for the default mode.
+    fn bound_alias_suffix(self) -> Option<&'static str> {
+        match self {
+            BindingMode::Default => None,
+            BindingMode::Ref | BindingMode::All => Some("Ref"),
+            BindingMode::Mut => Some("Mut"),
+            BindingMode::Move => Some("Owned"),
+        }
+    }
+}
+
+impl Parse for BindingMode {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(Token![ref]) {
+            input.parse::<Token![ref]>()?;
+            Ok(BindingMode::Ref)
+        } else if input.peek(Token![mut]) {
+            input.parse::<Token![mut]>()?;
+            Ok(BindingMode::Mut)
+        } else if input.peek(Token![move]) && !input.peek2(Token![|]) {
+            input.parse::<Token![move]>()?;
+            Ok(BindingMode::Move)
+        } else if input.fork().parse::<Ident>().is_ok_and(|i| i == "all")
+            && (input.peek2(Ident) || input.peek2(Token![self]))
+        {
+            // Two adjacent identifiers never form an expression
+            input.parse::<Ident>()?;
+            Ok(BindingMode::All)
+        } else {
+            Ok(BindingMode::Default)
+        }
+    }
+}
+
+/// A specialized arm written against a single variant, e.g. `Str(s) => ..`.
+///
+/// The variant may be qualified with the enum path, e.g.
+/// `shapes::Shape::Circle(r) => ..`, for matchers invoked outside the enum's
+/// module.
+struct VariantArm {
+    enum_path: Option<Path>,
+    variant_ident: Ident,
+    /// The `(..)` or `{..}` part of the pattern, if any.
+    fields: Option<proc_macro2::TokenTree>,
+    guard: Option<Expr>,
+    body: Expr,
+}
+
+impl Parse for VariantArm {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut enum_path = Path::parse_mod_style(input)?;
+        let variant_ident =
+            enum_path.segments.pop().unwrap().into_value().ident;
+        // Drop the trailing `::` left behind by the variant
+        let enum_path = (!enum_path.segments.is_empty()).then(|| {
+            let segments = enum_path
+                .segments
+                .into_pairs()
+                .map(|pair| pair.into_value());
+            Path {
+                leading_colon: enum_path.leading_colon,
+                segments: segments.collect(),
+            }
+        });
+        let fields =
+            if input.peek(syn::token::Paren) || input.peek(syn::token::Brace) {
+                Some(input.parse()?)
+            } else {
+                None
+            };
+        let guard = if input.peek(Token![if]) {
+            input.parse::<Token![if]>()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        input.parse::<Token![=>]>()?;
+        let body = input.parse()?;
+        Ok(VariantArm {
+            enum_path,
+            variant_ident,
+            fields,
+            guard,
+            body,
+        })
+    }
+}
+
+/// An entry of the `{ .. }` arm list.
+enum ArmEntry {
+    /// `Variant(pat) => body`
+    Variant(VariantArm),
+    /// `Group: Enum<..> => body`, expanded for the variants of a group.
+    Group(Ident, MatchArm),
+    /// `_: Enum<..> => body`, expanded for every variant not matched by
+    /// another arm.
+    Rest(Token![_], MatchArm),
+}
+
+impl Parse for ArmEntry {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let is_selector = input.peek2(Token![:]) && !input.peek2(Token![::]);
+        if input.peek(Token![_]) && is_selector {
+            let underscore = input.parse()?;
+            input.parse::<Token![:]>()?;
+            Ok(ArmEntry::Rest(underscore, input.parse()?))
+        } else if is_selector {
+            let group_name = input.parse()?;
+            input.parse::<Token![:]>()?;
+            Ok(ArmEntry::Group(group_name, input.parse()?))
+        } else {
+            Ok(ArmEntry::Variant(input.parse()?))
+        }
+    }
+}
+
+/// The arms of a matcher invocation.
+enum MatchArms {
+    /// A single generic arm expanded for every variant.
+    Single(MatchArm),
+    /// A `{ .. }` list of variant, group and fallback arms.
+    List(Span, Vec<ArmEntry>),
+}
+
+impl Parse for MatchArms {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if !input.peek(syn::token::Brace) {
+            return Ok(MatchArms::Single(input.parse()?));
+        }
+
+        let content;
+        let brace = braced!(content in input);
+        let mut entries = Vec::new();
+        while !content.is_empty() {
+            let entry: ArmEntry = content.parse()?;
+            let body = match &entry {
+                ArmEntry::Variant(arm) => &arm.body,
+                ArmEntry::Group(_, arm) | ArmEntry::Rest(_, arm) => &arm.body,
+            };
+            // Like `match`, the comma is optional after a block body.
+            let is_block = matches!(body, Expr::Block(_));
+            entries.push(entry);
+            if content.is_empty() {
+                break;
+            }
+            if is_block {
+                content.parse::<Option<Token![,]>>()?;
+            } else {
+                content.parse::<Token![,]>()?;
+            }
+        }
+        Ok(MatchArms::List(brace.span.join(), entries))
+    }
+}
+
+/// The full `dtype_match!` input.
+struct DTypeMatchInput {
+    metadata: MatcherMetadata,
+    binding_mode: BindingMode,
+    value: Expr,
+    arms: MatchArms,
+}
+
+impl Parse for DTypeMatchInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let metadata: MatcherMetadata = input.parse()?;
+        let binding_mode = input.parse()?;
+        let value = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let mut arms: MatchArms = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+
+        // Arms name the mirror of a remote enum, but match the remote enum
+        if let Some(remote) = &metadata.remote {
+            let sources: Vec<&mut EnumSpec> = match &mut arms {
+                MatchArms::Single(arm) => vec![&mut arm.source.spec],
+                MatchArms::List(_, entries) => entries
+                    .iter_mut()
+                    .filter_map(|entry| match entry {
+                        ArmEntry::Group(_, arm) | ArmEntry::Rest(_, arm) => {
+                            Some(&mut arm.source.spec)
+                        }
+                        ArmEntry::Variant(_) => None,
+                    })
+                    .collect(),
+            };
+            for spec in sources {
+                spec.remote = Some(remote.clone());
+            }
+        }
+        Ok(DTypeMatchInput {
+            metadata,
+            binding_mode,
+            value,
+            arms,
+        })
+    }
+}
+
+/// The `set Enum(Group | Variant)` form, building the `VariantSet` of the
+/// named groups and variants instead of matching a value.
+struct VariantSetInput {
+    metadata: MatcherMetadata,
+    enum_path: Path,
+    names: Vec<Ident>,
+}
+
+/// Either form of the `dtype_match!` input.
+enum DTypeMatchRequest {
+    Match(DTypeMatchInput),
+    Set(VariantSetInput),
+}
+
+impl Parse for DTypeMatchRequest {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let fork = input.fork();
+        fork.parse::<MatcherMetadata>()?;
+        // Two adjacent identifiers never form an expression
+        let is_set = fork.fork().parse::<Ident>().is_ok_and(|i| i == "set")
+            && fork.peek2(Ident);
+        if !is_set {
+            return input.parse().map(DTypeMatchRequest::Match);
+        }
+
+        let metadata = input.parse()?;
+        input.parse::<Ident>()?;
+        let enum_path = input.parse::<syn::ExprPath>()?.path;
+        let content;
+        parenthesized!(content in input);
+        let names =
+            Punctuated::<Ident, Token![|]>::parse_separated_nonempty(&content)?;
+        if !content.is_empty() {
+            return Err(
+                content.error("expected `|` between groups and variants")
+            );
+        }
+        input.parse::<Option<Token![,]>>()?;
+        Ok(DTypeMatchRequest::Set(VariantSetInput {
+            metadata,
+            enum_path,
+            names: names.into_iter().collect(),
+        }))
+    }
+}
+
+//============================================================================
+// Code Generation
+//============================================================================
+
+/// Entry point of the `dtype_match!` proc macro.
+pub fn dtype_match_impl(input: TokenStream) -> TokenStream {
+    let expanded = match parse_macro_input!(input as DTypeMatchRequest) {
+        DTypeMatchRequest::Match(input) => expand_dtype_match(&input),
+        DTypeMatchRequest::Set(input) => expand_variant_set(&input),
+    };
+    match expanded {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Expands the `set` form into a `const`-evaluable `VariantSet` chain.
+fn expand_variant_set(input: &VariantSetInput) -> syn::Result<TokenStream2> {
+    let VariantSetInput {
+        metadata,
+        enum_path,
+        names,
+    } = input;
+
+    let enum_ident = &enum_path
+        .segments
+        .last()
+        .expect("paths have a segment")
+        .ident;
+    if *enum_ident != metadata.enum_name {
+        return Err(Error::new_spanned(
+            enum_path,
+            format!(
+                "this matcher matches `{}`, found `{}`",
+                metadata.enum_name, enum_ident
+            ),
+        ));
+    }
+    if metadata.remote.is_some() {
+        return Err(Error::new_spanned(
+            enum_path,
+            "variant sets need `VariantIndex`, which is not implemented for \
+             remote enums",
+        ));
+    }
+
+    // Names are groups of the matcher or variants of the enum
+    let mut selected: Vec<&VariantMetadata> = Vec::new();
+    for name in names {
+        let group = metadata.groups.iter().find(|(group, _)| group == name);
+        let variant_names: Vec<&Ident> = match group {
+            Some((_, group_variants)) => group_variants.iter().collect(),
+            None => vec![name],
+        };
+        for variant_name in variant_names {
+            let variant = metadata
+                .variants
+                .iter()
+                .find(|v| v.variant_ident == *variant_name)
+                .ok_or_else(|| {
+                    Error::new(
+                        name.span(),
+                        format!(
+                            "`{}` is neither a group of this matcher nor a \
+                             variant of `{}`",
+                            name, metadata.enum_name
+                        ),
+                    )
+                })?;
+            selected.push(variant);
+        }
+    }
+
+    let dtype_variant_path = &metadata.dtype_variant_path;
+    let words = metadata.variants.len().div_ceil(64).max(1);
+    let indices = selected.iter().map(|v| v.index);
+    let attrs = selected.iter().flat_map(|v| &v.attrs);
+    let set = batch_ident("set");
+    Ok(quote! {{
+        #(#attrs)*
+        let #set = #dtype_variant_path::VariantSet::<#enum_path, #words>::new()
+            #(.with::<<#enum_path as #dtype_variant_path::VariantTokenAt<#indices>>::Token>())*;
+        #set
+    }})
+}
+
+fn expand_dtype_match(input: &DTypeMatchInput) -> syn::Result<TokenStream2> {
+    let DTypeMatchInput {
+        metadata,
+        binding_mode,
+        value,
+        arms,
+    } = input;
+
+    let match_arms = match arms {
+        MatchArms::Single(arm) => {
+            check_source_enum(metadata, arm)?;
+            metadata
+                .variants
+                .iter()
+                .map(|variant| {
+                    expand_variant_arm(metadata, *binding_mode, arm, variant)
+                })
+                .collect()
+        }
+        MatchArms::List(span, entries) => {
+            expand_arm_list(metadata, *binding_mode, *span, entries)?
+        }
+    };
+
+    // Keep the user's expression intact regardless of operator precedence.
+    let value = Group::new(Delimiter::None, quote! { #value });
+    let scrutinee = binding_mode.scrutinee(&quote! { #value });
+
+    let matched = quote! {
+        match #scrutinee {
+            #(#match_arms)*
+        }
+    };
+    if *binding_mode != BindingMode::All {
+        return Ok(matched);
+    }
+
+    // Batches dispatch on their first element, and are empty otherwise
+    let items = batch_ident("items");
+    let first = batch_ident("first");
+    let dtype_variant_path = &metadata.dtype_variant_path;
+    Ok(quote! {{
+        let #items = &(#value)[..];
+        match #items.first() {
+            ::core::option::Option::None => ::core::result::Result::<
+                _,
+                #dtype_variant_path::VariantMismatch,
+            >::Ok(::core::option::Option::None),
+            ::core::option::Option::Some(#first) => #matched,
+        }
+    }})
+}
+
+/// Names a local of the batch and set expansions, out of reach of the
+/// user's code.
+fn batch_ident(name: &str) -> Ident {
+    Ident::new(&format!("__dtype_{}", name), Span::mixed_site())
+}
+
+/// Checks that a generic arm names the enum this matcher was generated for.
+fn check_source_enum(
+    metadata: &MatcherMetadata,
+    arm: &MatchArm,
+) -> syn::Result<()> {
+    let source_ident = arm.source.spec.enum_ident();
+    if *source_ident != metadata.enum_name {
+        return Err(Error::new_spanned(
+            &arm.source.spec.path,
+            format!(
+                "this matcher matches `{}`, found `{}`",
+                metadata.enum_name, source_ident
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Expands a `{ .. }` arm list. Variant arms are emitted as written; group
+/// and `_` arms are expanded only for the variants not already handled by an
+/// unguarded variant arm (or, for `_`, by a group arm).
+fn expand_arm_list(
+    metadata: &MatcherMetadata,
+    binding_mode: BindingMode,
+    span: Span,
+    entries: &[ArmEntry],
+) -> syn::Result<Vec<TokenStream2>> {
+    let mut errors: Option<Error> = None;
+    let mut push_error = |error: Error| match &mut errors {
+        Some(errors) => errors.combine(error),
+        None => errors = Some(error),
+    };
+
+    let variant_names: Vec<String> = metadata
+        .variants
+        .iter()
+        .map(|v| v.variant_ident.to_string())
+        .collect();
+
+    // Variants fully handled by a variant arm
+    let mut specialized = Vec::new();
+    // Variants handled by a group arm
+    let mut grouped = Vec::new();
+    let mut seen_groups: Vec<String> = Vec::new();
+    let mut rest_arm: Option<&Token![_]> = None;
+
+    for entry in entries {
+        match entry {
+            ArmEntry::Variant(arm) if binding_mode == BindingMode::All => {
+                push_error(Error::new(
+                    arm.variant_ident.span(),
+                    "variant arms cannot match batches; use a generic arm \
+                     such as `Enum<T, Token>(items) => ..`",
+                ));
+            }
+            ArmEntry::Variant(arm) => {
+                let name = arm.variant_ident.to_string();
+                let qualifier = arm
+                    .enum_path
+                    .as_ref()
+                    .and_then(|path| path.segments.last())
+                    .filter(|segment| segment.ident != metadata.enum_name);
+                if let Some(segment) = qualifier {
+                    push_error(Error::new(
+                        segment.ident.span(),
+                        format!(
+                            "this matcher matches `{}`, found `{}`",
+                            metadata.enum_name, segment.ident
+                        ),
+                    ));
+                } else if !variant_names.contains(&name) {
+                    push_error(Error::new(
+                        arm.variant_ident.span(),
+                        format!(
+                            "`{}` has no variant named `{}`",
+                            metadata.enum_name, name
+                        ),
+                    ));
+                } else if arm.guard.is_none() {
+                    specialized.push(name);
+                }
+            }
+            ArmEntry::Group(group_name, arm) => {
+                if let Err(e) = check_source_enum(metadata, arm) {
+                    push_error(e);
+                }
+                let group =
+                    metadata.groups.iter().find(|(name, _)| name == group_name);
+                match group {
+                    None => push_error(Error::new(
+                        group_name.span(),
+                        format!(
+                            "`{}` is not a group of this matcher",
+                            group_name
+                        ),
+                    )),
+                    Some(_)
+                        if seen_groups.contains(&group_name.to_string()) =>
+                    {
+                        push_error(Error::new(
+                            group_name.span(),
+                            format!("duplicate arm for group `{}`", group_name),
+                        ))
+                    }
+                    Some((_, group_variants)) => {
+                        seen_groups.push(group_name.to_string());
+                        grouped.extend(
+                            group_variants.iter().map(Ident::to_string),
+                        );
+                    }
+                }
+            }
+            ArmEntry::Rest(underscore, arm) => {
+                if let Err(e) = check_source_enum(metadata, arm) {
+                    push_error(e);
+                }
+                if rest_arm.is_some() {
+                    push_error(Error::new(
+                        underscore.span,
+                        "duplicate `_` fallback arm",
+                    ));
+                }
+                rest_arm = Some(underscore);
+            }
+        }
+    }
+
+    // Without a fallback every variant must be handled by some arm
+    if rest_arm.is_none() {
+        for (group_name, group_variants) in &metadata.groups {
+            let missing = !seen_groups.contains(&group_name.to_string())
+                && group_variants
+                    .iter()
+                    .any(|v| !specialized.contains(&v.to_string()));
+            if missing {
+                push_error(Error::new(
+                    span,
+                    format!(
+                        "missing arm for group `{}`; add it or a `_: ..` fallback arm",
+                        group_name
+                    ),
+                ));
+            }
+        }
+        if metadata.groups.is_empty() {
+            for name in &variant_names {
+                if !specialized.contains(name) {
+                    push_error(Error::new(
+                        span,
+                        format!(
+                            "variant `{}` is not covered; add an arm for it or a `_: ..` fallback arm",
+                            name
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    if let Some(errors) = errors {
+        return Err(errors);
+    }
+
+    // Unqualified variant arms name the enum the way the generic or qualified
+    // arms do, so they resolve from wherever the matcher is invoked.
+    let enum_path = entries
+        .iter()
+        .find_map(|entry| match entry {
+            ArmEntry::Group(_, arm) | ArmEntry::Rest(_, arm) => {
+                Some(arm.source.spec.expr_path())
+            }
+            ArmEntry::Variant(arm) => {
+                arm.enum_path.as_ref().map(ToTokens::to_token_stream)
+            }
+        })
+        .unwrap_or_else(|| metadata.enum_name.to_token_stream());
+    let enum_path = match &metadata.remote {
+        Some(remote) => remote.to_token_stream(),
+        None => enum_path,
+    };
+    let expand_for = |arm: &MatchArm, include: &dyn Fn(&String) -> bool| {
+        metadata
+            .variants
+            .iter()
+            .filter(|variant| {
+                let name = variant.variant_ident.to_string();
+                !specialized.contains(&name) && include(&name)
+            })
+            .map(|variant| {
+                expand_variant_arm(metadata, binding_mode, arm, variant)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    Ok(entries
+        .iter()
+        .flat_map(|entry| match entry {
+            ArmEntry::Variant(VariantArm {
+                enum_path: qualified_path,
+                variant_ident,
+                fields,
+                guard,
+                body,
+            }) => {
+                let guard = guard.as_ref().map(|guard| quote! { if #guard });
+                let enum_path = qualified_path
+                    .as_ref()
+                    .filter(|_| metadata.remote.is_none())
+                    .map_or_else(|| enum_path.clone(), ToTokens::to_token_stream);
+                let attrs = metadata
+                    .variants
+                    .iter()
+                    .find(|v| v.variant_ident == *variant_ident)
+                    .map(|v| &v.attrs);
+                let attrs = attrs.into_iter().flatten();
+                vec![quote! { #(#attrs)* #enum_path::#variant_ident #fields #guard => #body, }]
+            }
+            ArmEntry::Group(group_name, arm) => {
+                let (_, group_variants) = metadata
+                    .groups
+                    .iter()
+                    .find(|(name, _)| name == group_name)
+                    .unwrap();
+                expand_for(arm, &|name| {
+                    group_variants.iter().any(|v| v == name)
+                })
+            }
+            ArmEntry::Rest(_, arm) => {
+                expand_for(arm, &|name| !grouped.contains(name))
+            }
+        })
+        .collect())
+}
+
+/// Generates `Enum::Variant(..) => { aliases; binding; body }` for one variant.
+fn expand_variant_arm(
+    metadata: &MatcherMetadata,
+    binding_mode: BindingMode,
+    arm: &MatchArm,
+    variant: &VariantMetadata,
+) -> TokenStream2 {
+    let dtype_variant_path = &metadata.dtype_variant_path;
+    let enum_ident = arm.source.spec.expr_path();
+    let variant_ident = &variant.variant_ident;
+    let token_path = arm.source.spec.token_path(metadata, variant);
+
+    // --- Pattern and inner binding ---
+    // Batches bind every element after the match, see below.
+    let arm_binding = match binding_mode {
+        BindingMode::All => &None,
+        _ => &arm.binding,
+    };
+    let (pattern, inner_binding) = match (&variant.kind, arm_binding) {
+        (VariantKind::Unit, None) => {
+            (quote! { #enum_ident::#variant_ident }, quote! {})
+        }
+        (VariantKind::Unit, Some(binding)) => (
+            quote! { #enum_ident::#variant_ident },
+            quote! {
+                #[allow(unused_variables, clippy::let_unit_value)]
+                let #binding = ();
+            },
+        ),
+        (VariantKind::Tuple, None) => {
+            (quote! { #enum_ident::#variant_ident(_) }, quote! {})
+        }
+        (VariantKind::Tuple, Some(binding)) => {
+            (quote! { #enum_ident::#variant_ident(#binding) }, quote! {})
+        }
+        (VariantKind::Struct { fields, .. }, Some(binding))
+            if binding_mode != BindingMode::Default =>
+        {
+            // Bind every field so the wrapper struct can be built. The
+            // bindings are mixed-site so they never shadow user variables.
+            let bindings: Vec<_> = fields
+                .iter()
+                .map(|field| Ident::new(&field.to_string(), Span::mixed_site()))
+                .collect();
+            let (wrapper_declaration, wrapper) = wrapper_struct(
+                metadata,
+                arm,
+                binding_mode,
+                variant,
+                &token_path,
+            );
+            (
+                quote! { #enum_ident::#variant_ident { #(#fields: #bindings),* } },
+                quote! {
+                    #wrapper_declaration
+                    #[allow(unused_variables)]
+                    let #binding = #wrapper { #(#fields: #bindings),* };
+                },
+            )
+        }
+        // With default binding modes the fields may be bound either by value
+        // or by reference, so no wrapper struct can be built.
+        (VariantKind::Struct { .. }, _) => {
+            (quote! { #enum_ident::#variant_ident { .. } }, quote! {})
+        }
+    };
+
+    // --- Source type declarations ---
+    let token_alias = &arm.source.token_alias;
+    let mut type_declarations = quote! {
+        #[allow(unused)] type #token_alias = #token_path;
+    };
+    if let Some(src_alias) = &arm.source.src_alias {
+        // Payload types are named through the enum's trait impls, which
+        // resolve them where the enum is defined.
+        let ident = &src_alias.ident;
+        let (generics, source_enum) = arm
+            .source
+            .spec
+            .type_for_alias(src_alias, metadata.lifetimes);
+        type_declarations.extend(quote! {
+            #[allow(unused)]
+            type #ident #generics = <#source_enum as #dtype_variant_path::VariantPayload<#token_path>>::Inner;
+        });
+
+        // Names the type the inner value is actually bound as, e.g.
+        // `SrcRef<'target> = &'target Vec<T>` for `ref` matching.
+        if let Some(suffix) = binding_mode.bound_alias_suffix() {
+            let bound_ident = format_ident!("{}{}", ident, suffix);
+            let bound_type = bound_type(
+                variant,
+                binding_mode,
+                &source_enum,
+                &token_path,
+                dtype_variant_path,
+            );
+            let mut bound_generics = generics.clone();
+            if binding_mode != BindingMode::Move {
+                bound_generics.params.insert(
+                    0,
+                    GenericParam::Lifetime(LifetimeParam::new(Lifetime::new(
+                        "'target",
+                        Span::call_site(),
+                    ))),
+                );
+            }
+            type_declarations.extend(quote! {
+                #[allow(unused)] type #bound_ident #bound_generics = #bound_type;
+            });
+        }
+    }
+
+    // --- Dest type/constraint declarations ---
+    for dest in &arm.dests {
+        let dest_alias = &dest.dest_alias;
+        let ident = &dest_alias.ident;
+        let (generics, dest_enum) = dest.spec.type_for_alias(dest_alias, 0);
+        type_declarations.extend(quote! {
+            #[allow(unused)]
+            type #ident #generics = <#dest_enum as #dtype_variant_path::EnumVariantDowncast<#token_path>>::Target;
+        });
+        if let Some(constraint_alias) = &dest.constraint_alias {
+            let ident = &constraint_alias.ident;
+            let (generics, dest_enum) =
+                dest.spec.type_for_alias(constraint_alias, 0);
+            type_declarations.extend(quote! {
+                #[allow(unused)]
+                type #ident #generics = <#dest_enum as #dtype_variant_path::EnumVariantConstraint<#token_path>>::Constraint;
+            });
+        }
+    }
+
+    // --- Where clause ---
+    // Checked per variant through a non-generic function so that an unmet
+    // bound is reported at the user's where clause rather than in the body.
+    let where_check = arm.where_clause.as_ref().map(|where_clause| {
+        let check_ident = Ident::new("__dtype_match_where", Span::mixed_site());
+        quote! {
+            #[allow(dead_code)]
+            fn #check_ident() #where_clause {}
+        }
+    });
+
+    let body = match &arm.body {
+        // Allow `{ body }` even if it's just `{}`
+        body @ Expr::Block(_) => quote! { #[allow(unused_braces)] #body },
+        body => quote! { #body },
+    };
+    let attrs = &variant.attrs;
+    if binding_mode == BindingMode::All {
+        let batch = batch_binding(metadata, variant, &pattern, &token_path);
+        let binding = match &arm.binding {
+            Some(binding) => quote! { #binding },
+            None => quote! { _ },
+        };
+        return quote! {
+            #(#attrs)*
+            #pattern => match #batch {
+                ::core::result::Result::Ok(#binding) => {
+                    #type_declarations
+                    #where_check
+
+                    ::core::result::Result::Ok(::core::option::Option::Some(#body))
+                }
+                ::core::result::Result::Err(error) => ::core::result::Result::Err(error),
+            },
+        };
+    }
+    quote! {
+        #(#attrs)*
+        #pattern => {
+            #inner_binding
+            #type_declarations
+            #where_check
+
+            #body
+        }
+    }
+}
+
+/// Returns the expression downcasting every element of a batch to the variant
+/// matched by `pattern`, as a `Result<Vec<_>, VariantMismatch>`.
+fn batch_binding(
+    metadata: &MatcherMetadata,
+    variant: &VariantMetadata,
+    pattern: &TokenStream2,
+    token_path: &TokenStream2,
+) -> TokenStream2 {
+    let dtype_variant_path = &metadata.dtype_variant_path;
+    let items = batch_ident("items");
+    match variant.kind {
+        // Unit variants have no downcasts; their elements bind as `()`
+        VariantKind::Unit => quote! {
+            match #items.iter().position(|item| !matches!(item, #pattern)) {
+                ::core::option::Option::Some(index) => ::core::result::Result::Err(
+                    #dtype_variant_path::VariantMismatch { index },
+                ),
+                ::core::option::Option::None => {
+                    ::core::result::Result::Ok(::std::vec![(); #items.len()])
+                }
+            }
+        },
+        _ => quote! {
+            #dtype_variant_path::downcast_all::<#token_path, _>(#items)
+        },
+    }
+}
+
+/// Names the struct a struct variant is bound as: the `Fields`, `Ref` or
+/// `Mut` struct, through an alias of the enum's downcast impls, or by path
+/// next to the enum for generic enums. Returns the alias declaration, if
+/// any, and the path to construct the struct with.
+fn wrapper_struct(
+    metadata: &MatcherMetadata,
+    arm: &MatchArm,
+    binding_mode: BindingMode,
+    variant: &VariantMetadata,
+    token_path: &TokenStream2,
+) -> (TokenStream2, TokenStream2) {
+    let wrapper_ident = Ident::new("__DTypeWrapper", Span::mixed_site());
+    let dtype_variant_path = &metadata.dtype_variant_path;
+    let spec = &arm.source.spec;
+    match spec.projected_type(metadata, true) {
+        Some(enum_type) => {
+            let (generics, wrapper) = match binding_mode {
+                BindingMode::Move | BindingMode::Default => (
+                    None,
+                    quote! {
+                        <#enum_type as #dtype_variant_path::EnumVariantDowncast<#token_path>>::Target
+                    },
+                ),
+                _ => (
+                    Some(quote! { <'target> }),
+                    bound_type(
+                        variant,
+                        binding_mode,
+                        &enum_type,
+                        token_path,
+                        dtype_variant_path,
+                    ),
+                ),
+            };
+            (
+                quote! {
+                    #[allow(unused)]
+                    type #wrapper_ident #generics = #wrapper;
+                },
+                quote! { #wrapper_ident },
+            )
+        }
+        None => {
+            let wrapper = struct_constructor(variant, binding_mode)
+                .map(|ident| spec.sibling_path(ident));
+            (quote! {}, quote! { #wrapper })
+        }
+    }
+}
+
+/// Returns the generated struct a struct variant is bound as in `binding_mode`.
+fn struct_constructor(
+    variant: &VariantMetadata,
+    binding_mode: BindingMode,
+) -> Option<&Ident> {
+    match (&variant.kind, binding_mode) {
+        (VariantKind::Struct { ref_ident, .. }, BindingMode::Ref) => {
+            Some(ref_ident)
+        }
+        (VariantKind::Struct { mut_ident, .. }, BindingMode::Mut) => {
+            Some(mut_ident)
+        }
+        (VariantKind::Struct { fields_ident, .. }, _) => Some(fields_ident),
+        _ => None,
+    }
+}
+
+/// Returns the type the inner value of a variant is bound as in the given
+/// binding mode, using `'target` as the borrow lifetime.
+fn bound_type(
+    variant: &VariantMetadata,
+    binding_mode: BindingMode,
+    source_enum: &TokenStream2,
+    token_path: &TokenStream2,
+    dtype_variant_path: &TokenStream2,
+) -> TokenStream2 {
+    match (&variant.kind, binding_mode) {
+        (VariantKind::Unit, _) => quote! { () },
+        (_, BindingMode::Ref | BindingMode::All) => quote! {
+            <#source_enum as #dtype_variant_path::EnumVariantDowncastRef<#token_path>>::Target<'target>
+        },
+        (_, BindingMode::Mut) => quote! {
+            <#source_enum as #dtype_variant_path::EnumVariantDowncastMut<#token_path>>::Target<'target>
+        },
+        _ => quote! {
+            <#source_enum as #dtype_variant_path::EnumVariantDowncast<#token_path>>::Target
+        },
+    }
+}
@@ -0,0 +1,224 @@
+use darling::{FromDeriveInput, FromField};
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use std::collections::HashMap;
+use syn::ext::IdentExt as _;
+use syn::{DeriveInput, Error, Generics, Ident, Path, Type, parse_macro_input};
+
+use crate::dtype_variant_path;
+
+/// Parses the `#[dtype(...)]` attribute of a `#[derive(DTypeFields)]` struct.
+#[derive(Debug, FromDeriveInput)]
+#[darling(attributes(dtype), supports(struct_named))]
+struct DTypeFieldsArgs {
+    ident: Ident,
+    generics: Generics,
+    data: darling::ast::Data<(), DTypeFieldArgs>,
+
+    /// Optional. Path to the module containing the tokens, as for
+    /// `#[derive(DType)]`. Defaults to the module of the struct.
+    #[darling(default)]
+    shared_variant_zst_path: Option<Path>,
+
+    /// Optional. A `DType` enum over the same tokens, which generates the
+    /// `get_by_kind` methods.
+    /// Example: `kind = DPrimType`
+    #[darling(default)]
+    kind: Option<Path>,
+
+    /// Optional. Path to the `dtype_variant` crate.
+    #[darling(default, rename = "crate")]
+    crate_path: Option<Path>,
+}
+
+/// Parses the `#[dtype(...)]` attribute of a field.
+#[derive(Debug, FromField)]
+#[darling(attributes(dtype))]
+struct DTypeFieldArgs {
+    ident: Option<Ident>,
+    ty: Type,
+
+    /// Optional. The variant name of the field's token, overriding the one
+    /// derived from the field name (`I32` for `I32Variant`).
+    /// Example: `#[dtype(variant = I32)]`
+    #[darling(default)]
+    variant: Option<Ident>,
+}
+
+pub fn dtype_fields_derive_impl(input: TokenStream) -> TokenStream {
+    let derive_input = parse_macro_input!(input as DeriveInput);
+    let args = match DTypeFieldsArgs::from_derive_input(&derive_input) {
+        Ok(args) => args,
+        Err(e) => return e.write_errors().into(),
+    };
+    match expand(args) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(args: DTypeFieldsArgs) -> syn::Result<TokenStream2> {
+    let dtype_variant_path = match &args.crate_path {
+        Some(path) => path.clone(),
+        None => dtype_variant_path(args.ident.span())?,
+    };
+    let tokens_path = args
+        .shared_variant_zst_path
+        .clone()
+        .unwrap_or_else(|| format_ident!("self").into());
+    let struct_name = &args.ident;
+    let (impl_generics, ty_generics, where_clause) =
+        args.generics.split_for_impl();
+
+    let fields = args
+        .data
+        .take_struct()
+        .expect("darling only accepts named structs")
+        .fields;
+
+    // Each token may index a single field.
+    let mut seen: HashMap<Ident, &Ident> = HashMap::new();
+    let mut errors: Option<Error> = None;
+    let mut entries = Vec::new();
+    for field in &fields {
+        let field_ident = field.ident.as_ref().expect("named fields");
+        let variant = field.variant.clone().unwrap_or_else(|| {
+            let name = to_pascal_case(&field_ident.unraw().to_string());
+            Ident::new(&name, field_ident.span())
+        });
+        let token_ident =
+            format_ident!("{}Variant", variant, span = variant.span());
+        if let Some(previous) = seen.insert(token_ident.clone(), field_ident) {
+            let error = Error::new_spanned(
+                field_ident,
+                format!(
+                    "DTypeFields validation: fields `{}` and `{}` are both \
+                     indexed by `{}`; pick another token with \
+                     `#[dtype(variant = ...)]`",
+                    previous, field_ident, token_ident
+                ),
+            );
+            errors = Some(match errors {
+                Some(mut existing) => {
+                    existing.combine(error);
+                    existing
+                }
+                None => error,
+            });
+        }
+        entries.push((field_ident, &field.ty, token_ident));
+    }
+    if let Some(errors) = errors {
+        return Err(errors);
+    }
+
+    let field_impls = entries.iter().map(|(field_ident, ty, token_ident)| {
+        quote! {
+            impl #impl_generics #dtype_variant_path::TokenField<#tokens_path::#token_ident>
+                for #struct_name #ty_generics #where_clause
+            {
+                type Field = #ty;
+
+                fn field(&self) -> &Self::Field {
+                    &self.#field_ident
+                }
+
+                fn field_mut(&mut self) -> &mut Self::Field {
+                    &mut self.#field_ident
+                }
+            }
+        }
+    });
+
+    let kind_methods = args.kind.as_ref().map(|kind| {
+        // The kind's variant for a token is found by constructing it, so
+        // every indexed token must name a unit variant of the kind.
+        let token_indices = entries.iter().map(|(_, _, token_ident)| {
+            quote! {
+                #dtype_variant_path::VariantIndex::variant_index(
+                    &<#kind as #dtype_variant_path::EnumVariantConstruct<#tokens_path::#token_ident>>::construct(())
+                )
+            }
+        });
+        let token_indices: Vec<_> = token_indices.collect();
+        let field_idents: Vec<_> = entries.iter().map(|(f, _, _)| f).collect();
+        // Higher-ranked so that non-`'static` fields only disable the methods.
+        let any_bounds = entries
+            .iter()
+            .map(|(_, ty, _)| quote! { for<'__any> #ty: ::core::any::Any });
+        let any_bounds: Vec<_> = any_bounds.collect();
+
+        quote! {
+            impl #impl_generics #struct_name #ty_generics #where_clause {
+                /// Returns the field indexed by the token of `kind`, or `None`
+                /// if no field has that token.
+                pub fn get_by_kind(&self, kind: &#kind) -> Option<&dyn ::core::any::Any>
+                where
+                    #(#any_bounds,)*
+                {
+                    let index = #dtype_variant_path::VariantIndex::variant_index(kind);
+                    #(
+                        if index == #token_indices {
+                            return Some(&self.#field_idents);
+                        }
+                    )*
+                    None
+                }
+
+                /// Returns the field indexed by the token of `kind` mutably, or
+                /// `None` if no field has that token.
+                pub fn get_by_kind_mut(&mut self, kind: &#kind) -> Option<&mut dyn ::core::any::Any>
+                where
+                    #(#any_bounds,)*
+                {
+                    let index = #dtype_variant_path::VariantIndex::variant_index(kind);
+                    #(
+                        if index == #token_indices {
+                            return Some(&mut self.#field_idents);
+                        }
+                    )*
+                    None
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics #struct_name #ty_generics #where_clause {
+            /// Returns the field indexed by `Token`.
+            pub fn get<Token>(&self) -> &<Self as #dtype_variant_path::TokenField<Token>>::Field
+            where
+                Self: #dtype_variant_path::TokenField<Token>,
+            {
+                <Self as #dtype_variant_path::TokenField<Token>>::field(self)
+            }
+
+            /// Returns the field indexed by `Token` mutably.
+            pub fn get_mut<Token>(&mut self) -> &mut <Self as #dtype_variant_path::TokenField<Token>>::Field
+            where
+                Self: #dtype_variant_path::TokenField<Token>,
+            {
+                <Self as #dtype_variant_path::TokenField<Token>>::field_mut(self)
+            }
+        }
+
+        #kind_methods
+
+        #(#field_impls)*
+    })
+}
+
+/// Converts a `snake_case` field name to the `PascalCase` variant name of its
+/// token (`player_move` becomes `PlayerMove`, `i32` becomes `I32`).
+fn to_pascal_case(name: &str) -> String {
+    name.split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
@@ -1,15 +1,33 @@
 use proc_macro::TokenStream;
-use quote::{format_ident, quote};
+use proc_macro2::Span;
+use quote::{ToTokens, format_ident, quote};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use syn::{Ident, Token, parse_macro_input, punctuated::Punctuated};
 
+mod delegate;
 mod derive;
+mod dtype_match;
+mod fields;
 mod grouped_matcher;
-mod matcher_gen;
 
-pub(crate) fn dtype_variant_path() -> syn::Path {
+/// Resolves the path to the `dtype_variant` crate from the caller's manifest.
+///
+/// Fails with an error at `span` if `dtype_variant` is not a dependency, e.g.
+/// when it is only re-exported through a facade crate.
+pub(crate) fn dtype_variant_path(span: Span) -> syn::Result<syn::Path> {
     let found_crate = proc_macro_crate::crate_name("dtype_variant")
-        .expect("dtype_variant is present in `Cargo.toml`");
-    match found_crate {
+        .map_err(|e| {
+            syn::Error::new(
+                span,
+                format!(
+                    "cannot find the `dtype_variant` crate ({}); if it is \
+                     re-exported, point to it with `#[dtype(crate = path::to::dtype_variant)]`",
+                    e
+                ),
+            )
+        })?;
+    Ok(match found_crate {
         proc_macro_crate::FoundCrate::Itself => format_ident!("crate").into(),
         proc_macro_crate::FoundCrate::Name(name) => {
             // Parse crate name safely - fall back to simple identifier if parsing fails
@@ -21,7 +39,20 @@ pub(crate) fn dtype_variant_path() -> syn::Path {
                 ))
             })
         }
-    }
+    })
+}
+
+/// Hashes `item` along with the site of `span`, for the names of hidden
+/// `#[macro_export]` macros, which all share the crate root.
+///
+/// The `Debug` form of a span holds its position in the crate's sources and
+/// its expansion, so identical items in different modules get different
+/// names.
+pub(crate) fn site_hash(item: &impl ToTokens, span: Span) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    item.to_token_stream().to_string().hash(&mut hasher);
+    format!("{:?}", span).hash(&mut hasher);
+    hasher.finish()
 }
 
 #[proc_macro_derive(DType, attributes(dtype, dtype_grouped_matcher))]
@@ -29,6 +60,41 @@ pub fn dtype_derive(input: TokenStream) -> TokenStream {
     derive::dtype_derive_impl(input)
 }
 
+/// Indexes the fields of a struct by dtype tokens, one token per field.
+#[proc_macro_derive(DTypeFields, attributes(dtype))]
+pub fn dtype_fields_derive(input: TokenStream) -> TokenStream {
+    fields::dtype_fields_derive_impl(input)
+}
+
+/// Expands the matcher macros generated by `#[derive(DType)]`.
+///
+/// Not meant to be invoked directly: the generated matchers prepend the
+/// enum metadata this macro needs to the user's input.
+#[doc(hidden)]
+#[proc_macro]
+pub fn dtype_match(input: TokenStream) -> TokenStream {
+    dtype_match::dtype_match_impl(input)
+}
+
+/// Makes a trait delegable with `#[dtype(delegate(Trait))]`.
+///
+/// Defines a macro of the same name next to the trait, carrying the method
+/// signatures the derive needs to forward them to variant payloads.
+#[proc_macro_attribute]
+pub fn dtype_delegate(attr: TokenStream, item: TokenStream) -> TokenStream {
+    delegate::dtype_delegate_attr_impl(attr, item)
+}
+
+/// Expands the delegation macros defined by `#[dtype_delegate]`.
+///
+/// Not meant to be invoked directly: those macros combine the trait's
+/// signatures with the impl header and variants passed by the derive.
+#[doc(hidden)]
+#[proc_macro]
+pub fn dtype_delegate_impl(input: TokenStream) -> TokenStream {
+    delegate::dtype_delegate_impl(input)
+}
+
 struct DTypeInput {
     variants: Punctuated<Ident, Token![,]>,
 }
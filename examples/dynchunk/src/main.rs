@@ -7,7 +7,11 @@ impl DPrim for i32 {}
 impl DPrim for f32 {}
 
 #[derive(DType, Clone, Debug)]
-#[dtype(shared_variant_zst_path = self, matcher = match_dprimtype)]
+#[dtype(
+    shared_variant_zst_path = self,
+    payloads = DynChunk,
+    matcher = match_dprimtype
+)]
 enum DPrimType {
     I32,
     F32,
@@ -98,6 +102,9 @@ fn main() {
     let primitive_type = chunk2.prim_type();
     println!("Primitive type of chunk2: {:?}", primitive_type);
 
+    let kind = DPrimType::of::<Vec<f32>>();
+    println!("Primitive type of Vec<f32>: {:?}", kind);
+
     let empty = DPrimType::F32.create_chunk();
     println!("Primitive type of empty chunk: {:?}", empty.prim_type());
 